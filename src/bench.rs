@@ -0,0 +1,80 @@
+//! throughput benchmarks for the interpreter, run with `crabs bench`
+
+use crate::crab::Crab;
+use crate::level::{Grid, GRID_H, GRID_W};
+use crate::sim::{Outcome, Sim};
+use std::time::{Duration, Instant};
+
+/// programs that never end, each stressing a different part of the interpreter
+pub const BENCHMARKS: [(&str, &str); 5] = [
+    ("loop", "L:\nJMP L"),
+    ("arith", "L:\nADD 1 A\nSUB 1 A\nNEG A\nMOV A H\nJMP L"),
+    ("branch", "MOV 5 A\nL:\nSUB 1 A\nJGZ L\nMOV 5 A\nJMP L"),
+    ("jro", "MOV 2 A\nJRO A\nNOP\nJRO -2"),
+    ("walk", "MOV 1 M\nL:\nRCW\nJMP L"),
+];
+
+pub struct BenchResult {
+    pub name: &'static str,
+    pub cycles: usize,
+    /// time taken by `Crab` alone
+    pub crab: Duration,
+    /// time taken by `Sim`, which also collects tiles
+    pub sim: Duration,
+}
+
+impl BenchResult {
+    fn rate(&self, time: Duration) -> f64 {
+        self.cycles as f64 / time.as_secs_f64() / 1e6
+    }
+
+    /// million cycles per second for `Crab` and `Sim`
+    pub fn rates(&self) -> (f64, f64) {
+        (self.rate(self.crab), self.rate(self.sim))
+    }
+}
+
+/// a level with one tile in a corner none of the benchmarks reach, so runs never clear it
+fn grid() -> Grid {
+    let mut grid = vec![vec![false; GRID_W]; GRID_H];
+    grid[0][0] = true;
+    grid
+}
+
+/// runs one benchmark for `cycles` cycles
+pub fn bench(name: &'static str, code: &str, cycles: usize) -> Result<BenchResult, String> {
+    let mut crab = Crab::new();
+    crab.load_code(code).map_err(|line| format!("{}: line {} is not a valid instruction", name, line + 1))?;
+    let start = Instant::now();
+    let ran = crab.execute(cycles).map_err(|e| format!("{}: {}", name, e))?;
+    let crab_time = start.elapsed();
+    if ran != cycles {
+        return Err(format!("{}: stopped after {} cycles", name, ran));
+    }
+
+    let mut sim = Sim::new(grid());
+    sim.load_code(code).unwrap();
+    let start = Instant::now();
+    let outcome = sim.run(cycles);
+    let sim_time = start.elapsed();
+    if outcome != Outcome::Timeout(cycles) {
+        return Err(format!("{}: ended with {}", name, outcome));
+    }
+
+    Ok(BenchResult { name, cycles, crab: crab_time, sim: sim_time })
+}
+
+pub fn run_suite(cycles: usize) -> Result<Vec<BenchResult>, String> {
+    BENCHMARKS.iter().map(|(name, code)| bench(name, code, cycles)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suite() {
+        let results = run_suite(1000).unwrap();
+        assert_eq!(results.len(), BENCHMARKS.len());
+    }
+}
@@ -0,0 +1,370 @@
+//! headless commands, run instead of the game when arguments are given
+
+use crate::level;
+use crate::replay::Replay;
+use crate::lint::lint;
+use crate::crab::Crab;
+use crate::flow::{Cfg, CfgFormat};
+use crate::bench;
+use crate::syntax;
+use crate::isa;
+use crate::solver::{self, Profile, Verdict};
+use crate::synth::{self, Goal};
+use crate::generator::{self, Params};
+use crate::sim::{self, Outcome, Sim};
+use crate::trace::{Trace, TraceFormat};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+
+const USAGE: &str = "\
+usage:
+    crabs                       start the game
+    crabs run <level> <program> [--limit <cycles>]
+    crabs trace <level> <program> [--format jsonl|csv] [--out <file>] [--limit <cycles>] [--variant <n>]
+    crabs record <level> <program> [--out <file>]
+    crabs verify <replay>...
+    crabs check <program>...
+    crabs isa
+    crabs cfg <program> [--format dot|json] [--out <file>]
+    crabs bench [--cycles <n>]
+    crabs solve <level>... [--isa full|rcw,rcc,reverse] [--max-states <n>] [--program]
+    crabs synth <level>... [--goal shortest|fastest] [--max-len <n>] [--limit <cycles>]
+    crabs gen [--seed <n>] [--width <n>] [--height <n>] [--targets <n>] [--rotations <n>] [--out <file>]
+
+<level> is a level number starting at 1 or the path to a level file.";
+
+/// returns the exit code
+pub fn run(args: &[String]) -> i32 {
+    let (positional, options) = parse_args(&args[1..]);
+    let res = match args[0].as_str() {
+        "run" => run_level(&positional, &options),
+        "trace" => trace(&positional, &options),
+        "record" => record(&positional, &options),
+        "verify" => verify(&positional),
+        "check" => check(&positional),
+        "isa" => {
+            print!("{}", isa::reference());
+            Ok(())
+        }
+        "cfg" => cfg(&positional, &options),
+        "bench" => bench(&options),
+        "solve" => solve(&positional, &options),
+        "synth" => synth(&positional, &options),
+        "gen" => gen(&options),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        cmd => Err(format!("unknown command {}\n{}", cmd, USAGE)),
+    };
+    match res {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// options that take no value
+const FLAGS: [&str; 1] = ["program"];
+
+/// splits arguments into positional ones and `--key value` options
+fn parse_args(args: &[String]) -> (Vec<&str>, HashMap<&str, &str>) {
+    let mut positional = vec![];
+    let mut options = HashMap::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if let Some(key) = arg.strip_prefix("--") {
+            let val = if FLAGS.contains(&key) { "" } else { iter.next().map(|s| s.as_str()).unwrap_or("") };
+            options.insert(key, val);
+        } else {
+            positional.push(arg.as_str());
+        }
+    }
+    (positional, options)
+}
+
+fn load_program(sim: &mut Sim, path: &str) -> Result<(), String> {
+    let code = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    sim.load_code(&code.to_uppercase())
+        .map_err(|line| format!("{}:{}: not a valid instruction", path, line + 1))
+}
+
+/// the `--limit` option, falling back to the level's own limit
+fn limit(options: &HashMap<&str, &str>, default: usize) -> Result<usize, String> {
+    match options.get("limit") {
+        Some(n) => n.parse().map_err(|_| format!("invalid cycle limit {}", n)),
+        None => Ok(default),
+    }
+}
+
+/// runs a program on every variant of a level, fails unless all of them are cleared
+fn run_level(args: &[&str], options: &HashMap<&str, &str>) -> Result<(), String> {
+    let (level, program) = match args {
+        [level, program] => (level, program),
+        _ => return Err(USAGE.to_owned()),
+    };
+    let level = level::load(level)?;
+    let code = fs::read_to_string(program).map_err(|e| format!("cannot read {}: {}", program, e))?;
+    let outcomes = sim::run_level(&level, &code.to_uppercase(), limit(options, level.cycle_limit)?)
+        .map_err(|line| format!("{}:{}: not a valid instruction", program, line + 1))?;
+    for (i, outcome) in outcomes.iter().enumerate() {
+        let result = match outcome {
+            Outcome::Cleared(_) => "ok",
+            _ => "FAILED",
+        };
+        println!("variant {}: {}, {}", i + 1, result, outcome);
+    }
+    if !sim::all_cleared(&outcomes) {
+        let failed = outcomes.iter().filter(|o| !matches!(o, Outcome::Cleared(_))).count();
+        return Err(format!("{} of {} variants failed", failed, outcomes.len()));
+    }
+    Ok(())
+}
+
+fn trace(args: &[&str], options: &HashMap<&str, &str>) -> Result<(), String> {
+    let (level, program) = match args {
+        [level, program] => (level, program),
+        _ => return Err(USAGE.to_owned()),
+    };
+    let format = match options.get("format") {
+        Some(f) => f.parse()?,
+        None => TraceFormat::Jsonl,
+    };
+    let level = level::load(level)?;
+    let limit = limit(options, level.cycle_limit)?;
+    let variant: usize = number(options, "variant", 1)?;
+    let grid = variant.checked_sub(1).and_then(|i| level.grids.get(i))
+        .ok_or(format!("there is no variant {}, the level has {}", variant, level.grids.len()))?;
+    let mut sim = Sim::new(grid.clone());
+    sim.set_streams(level.input.clone(), level.output.clone());
+    load_program(&mut sim, program)?;
+    sim.trace = Some(Trace::new());
+    eprintln!("{}", sim.run(limit));
+
+    let trace = sim.trace.unwrap();
+    let res = match options.get("out") {
+        Some(path) => File::create(path).and_then(|mut f| trace.write(&mut f, format)),
+        None => {
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            trace.write(&mut out, format).and_then(|_| out.flush())
+        }
+    };
+    res.map_err(|e| format!("cannot write trace: {}", e))
+}
+
+fn record(args: &[&str], options: &HashMap<&str, &str>) -> Result<(), String> {
+    let (level, program) = match args {
+        [level, program] => (level, program),
+        _ => return Err(USAGE.to_owned()),
+    };
+    let code = fs::read_to_string(program).map_err(|e| format!("cannot read {}: {}", program, e))?;
+    let replay = Replay::record(level, &code.to_uppercase())?;
+    eprintln!("{}", sim::describe(&replay.outcomes));
+    match options.get("out") {
+        Some(path) => fs::write(path, replay.to_string()).map_err(|e| format!("cannot write {}: {}", path, e)),
+        None => {
+            print!("{}", replay);
+            Ok(())
+        }
+    }
+}
+
+fn verify(args: &[&str]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+    let mut failed = 0;
+    for path in args {
+        let res = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path, e))
+            .and_then(|file| file.parse::<Replay>())
+            .and_then(|replay| replay.verify().map(|_| replay));
+        match res {
+            Ok(replay) => println!("{}: ok, {}", path, sim::describe(&replay.outcomes)),
+            Err(e) => {
+                println!("{}: FAILED, {}", path, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} replays failed", failed, args.len()));
+    }
+    Ok(())
+}
+
+/// prints warnings for every program, fails only if one does not assemble
+fn check(args: &[&str]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+    let mut broken = 0;
+    for path in args {
+        let code = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?.to_uppercase();
+        let mut crab = Crab::new();
+        if crab.load_code(&code).is_err() {
+            for (i, line) in code.lines().enumerate() {
+                if let Err(e) = syntax::parse_line(line) {
+                    println!("{}:{}:{}: error: {}", path, i + 1, e.start + 1, e);
+                }
+            }
+            broken += 1;
+            continue;
+        }
+        for l in lint(&crab.code) {
+            println!("{}:{}: warning: {}", path, l.line + 1, l.kind);
+        }
+    }
+    if broken > 0 {
+        return Err(format!("{} of {} programs do not assemble", broken, args.len()));
+    }
+    Ok(())
+}
+
+fn cfg(args: &[&str], options: &HashMap<&str, &str>) -> Result<(), String> {
+    let path = match args {
+        [path] => path,
+        _ => return Err(USAGE.to_owned()),
+    };
+    let format = match options.get("format") {
+        Some(f) => f.parse()?,
+        None => CfgFormat::Dot,
+    };
+    let code = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?.to_uppercase();
+    let mut crab = Crab::new();
+    crab.load_code(&code).map_err(|line| format!("{}:{}: not a valid instruction", path, line + 1))?;
+    let source: Vec<_> = code.lines().map(|l| l.trim().to_owned()).collect();
+    let out = Cfg::build(&crab.code).export(&source, format);
+    match options.get("out") {
+        Some(out_path) => fs::write(out_path, out).map_err(|e| format!("cannot write {}: {}", out_path, e)),
+        None => {
+            print!("{}", out);
+            Ok(())
+        }
+    }
+}
+
+/// build with `--release`, the numbers mean little otherwise
+fn bench(options: &HashMap<&str, &str>) -> Result<(), String> {
+    let cycles = match options.get("cycles") {
+        Some(n) => n.parse().map_err(|_| format!("invalid cycle count {}", n))?,
+        None => 10_000_000,
+    };
+    println!("{:<8} {:>12} {:>14} {:>14}", "program", "cycles", "crab Mcyc/s", "sim Mcyc/s");
+    for result in bench::run_suite(cycles)? {
+        let (crab, sim) = result.rates();
+        println!("{:<8} {:>12} {:>14.1} {:>14.1}", result.name, result.cycles, crab, sim);
+    }
+    Ok(())
+}
+
+/// prints the fewest cycles each level can be cleared in, fails if one cannot be
+fn solve(args: &[&str], options: &HashMap<&str, &str>) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+    let profile = match options.get("isa") {
+        Some(isa) => isa.parse()?,
+        None => Profile::full(),
+    };
+    let max_states = match options.get("max-states") {
+        Some(n) => n.parse().map_err(|_| format!("invalid state count {}", n))?,
+        None => solver::MAX_STATES,
+    };
+    let mut unsolvable = 0;
+    for arg in args {
+        let level = level::load(arg)?;
+        if !solver::supports(&level) {
+            println!("{}: unsupported, the solver does not model IN and OUT", arg);
+            continue;
+        }
+        for (i, grid) in level.grids.iter().enumerate() {
+            let name = if level.grids.len() > 1 { format!("{} variant {}", arg, i + 1) } else { arg.to_string() };
+            let verdict = solver::solve(grid, profile, max_states)?;
+            println!("{}: {}", name, verdict);
+            match &verdict {
+                Verdict::Solvable { cycles, .. } if *cycles > level.cycle_limit => {
+                    println!("{}: warning: the cycle limit {} is below the minimum", name, level.cycle_limit);
+                }
+                Verdict::Unsolvable => unsolvable += 1,
+                _ => (),
+            }
+            if options.contains_key("program") {
+                if let Some(program) = verdict.program() {
+                    println!("{}", program);
+                }
+            }
+        }
+    }
+    if unsolvable > 0 {
+        return Err(format!("{} levels or variants are unsolvable", unsolvable));
+    }
+    Ok(())
+}
+
+/// prints the best small program for each level, fails if one has none
+fn synth(args: &[&str], options: &HashMap<&str, &str>) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+    let goal = match options.get("goal") {
+        Some(goal) => goal.parse()?,
+        None => Goal::Shortest,
+    };
+    let max_len = match options.get("max-len") {
+        Some(n) => n.parse().map_err(|_| format!("invalid program length {}", n))?,
+        None => synth::MAX_LEN,
+    };
+    let mut missing = 0;
+    for arg in args {
+        let level = level::load(arg)?;
+        match synth::synthesize(&level, max_len, goal, limit(options, level.cycle_limit)?) {
+            Some(found) => {
+                println!("{}: {} instructions, {} cycles", arg, found.instructions, found.cycles);
+                println!("{}", found.program);
+            }
+            None => {
+                println!("{}: no program of up to {} instructions", arg, max_len);
+                missing += 1;
+            }
+        }
+    }
+    if missing > 0 {
+        return Err(format!("no program found for {} of {} levels", missing, args.len()));
+    }
+    Ok(())
+}
+
+/// the numeric option `key`, or `default` when it is not given
+fn number<T: std::str::FromStr>(options: &HashMap<&str, &str>, key: &str, default: T) -> Result<T, String> {
+    match options.get(key) {
+        Some(n) => n.parse().map_err(|_| format!("invalid {} {}", key, n)),
+        None => Ok(default),
+    }
+}
+
+fn gen(options: &HashMap<&str, &str>) -> Result<(), String> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+    let seed = number(options, "seed", now.as_secs())?;
+    let default = Params::default();
+    let params = Params {
+        width: number(options, "width", default.width)?,
+        height: number(options, "height", default.height)?,
+        targets: number(options, "targets", default.targets)?,
+        rotations: number(options, "rotations", default.rotations)?,
+    };
+    let level = generator::generate(seed, &params)?;
+    eprintln!("seed {}", seed);
+    let file = format!("{}\ngenerated from seed {}\n", level::format(&level), seed);
+    match options.get("out") {
+        Some(path) => fs::write(path, file).map_err(|e| format!("cannot write {}: {}", path, e)),
+        None => {
+            print!("{}", file);
+            Ok(())
+        }
+    }
+}
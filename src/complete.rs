@@ -0,0 +1,94 @@
+//! suggestions for the word being typed in the editor
+
+use crate::crab::REGISTERS;
+use crate::isa::{Operand, INSTRUCTIONS};
+use crate::syntax::{self, TokenKind};
+
+/// ways to finish the word before the cursor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    /// where the word starts in the line
+    pub start: usize,
+    /// whole words that start with what has been typed
+    pub words: Vec<String>,
+    /// what the instruction on the line takes, None while typing the instruction itself
+    pub signature: Option<String>,
+}
+
+impl Completion {
+    /// what to type after the cursor to accept the first suggestion
+    ///
+    /// instructions with operands get a space after them
+    pub fn rest(&self, col: usize) -> String {
+        let word = &self.words[0];
+        let mut rest = word[(col - self.start).min(word.len())..].to_owned();
+        if self.signature.is_none() && !syntax::operands(word).unwrap_or(&[]).is_empty() {
+            rest.push(' ');
+        }
+        rest
+    }
+}
+
+/// names of the labels defined in a program
+pub fn labels(lines: &[String]) -> Vec<String> {
+    lines.iter()
+        .filter_map(|line| syntax::tokenize(line).into_iter().next())
+        .filter(|token| token.kind == TokenKind::Label)
+        .map(|token| token.text.trim_end_matches(':').to_owned())
+        .collect()
+}
+
+/// suggestions for the word that ends at `col`, None if there is nothing to suggest
+pub fn complete(line: &str, col: usize, labels: &[String]) -> Option<Completion> {
+    // only at the end of a word
+    if line[col..].starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    let tokens = syntax::tokenize(&line[..col]);
+    let word = tokens.last().filter(|t| t.end() == col && t.kind != TokenKind::Comment)?;
+    let (first, operand) = (&tokens[0], tokens.len() - 1);
+
+    let (candidates, signature): (Vec<String>, _) = if operand == 0 {
+        (INSTRUCTIONS.iter().map(|i| i.mnemonic.to_owned()).collect(), None)
+    } else {
+        let registers = || REGISTERS.iter().map(|r| format!("{:?}", r)).collect();
+        let candidates = match syntax::operands(first.text)?.get(operand - 1)? {
+            Operand::Label => labels.to_vec(),
+            Operand::Value | Operand::Register => registers(),
+        };
+        (candidates, syntax::signature(first.text))
+    };
+    let words: Vec<_> = candidates.into_iter().filter(|c| c.starts_with(word.text)).collect();
+    if words.is_empty() {
+        return None;
+    }
+    Some(Completion { start: word.start, words, signature })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete() {
+        let labels = labels(&["LOOP:".to_owned(), "JMP LOOP".to_owned(), "LATER:".to_owned()]);
+        assert_eq!(labels, vec!["LOOP", "LATER"]);
+
+        let c = complete("  J", 3, &labels).unwrap();
+        assert_eq!(c.words, vec!["JMP", "JEZ", "JNZ", "JGZ", "JLZ", "JRO"]);
+        assert_eq!(c.start, 2);
+        assert_eq!(c.rest(3), "MP ");
+
+        let c = complete("JGZ L", 5, &labels).unwrap();
+        assert_eq!(c.words, vec!["LOOP", "LATER"]);
+        assert_eq!(c.signature.as_deref(), Some("JGZ <label>"));
+        assert_eq!(c.rest(5), "OOP");
+
+        assert_eq!(complete("ADD 1 A", 7, &labels).unwrap().words, vec!["A"]);
+        // numbers, extra operands, comments and the middle of a word get nothing
+        assert_eq!(complete("ADD 1", 5, &labels), None);
+        assert_eq!(complete("NEG A A", 7, &labels), None);
+        assert_eq!(complete("NOP # J", 7, &labels), None);
+        assert_eq!(complete("JMP", 1, &labels), None);
+    }
+}
@@ -1,34 +1,197 @@
-use crate::game::Game;
-use std::collections::HashMap;
+use crate::flow::find_label;
+use crate::isa::Arg;
+use crate::syntax::parse_line;
+use std::fmt;
 use std::str::FromStr;
 
-const INIT_X: i32 = 3;
-const INIT_Y: i32 = 3;
+/// bumped whenever instructions or their semantics change, replays record it
+pub const ISA_VERSION: u32 = 3;
+
+/// tile every crab starts on
+pub const INIT_X: i32 = 3;
+pub const INIT_Y: i32 = 3;
+
+/// unit vector a positive `M` moves the crab in, indexed by `R`, see `Crab::dir`
+pub const DIRS: [(i32, i32); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
 
 #[derive(Debug)]
 pub struct Crab {
-    pub registers: HashMap<Register, i32>,
+    /// indexed by `Register::index`
+    pub registers: [i32; 5],
     /// instruction pointer
     pub ip: usize,
     pub code: Vec<OpCode>,
+    /// `code` decoded for execution
+    program: Vec<Instr>,
     pub pos_y: i32,
     pub pos_x: i32,
+    /// values `IN` reads, in order
+    pub input: Vec<i32>,
+    /// index of the next value `IN` reads
+    pub input_pos: usize,
+    /// values written by `OUT`
+    pub output: Vec<i32>,
+}
+
+/// why a program stopped before running off its end
+///
+/// lines are instruction indices, shown to the player starting at 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// stepped a crab whose program already ended
+    IpOutOfBounds { ip: usize },
+    /// relative jump on line `from` to `to`, which is outside the program
+    BadJumpTarget { from: usize, to: i64 },
+    /// jump on `line` to a label that does not exist
+    UnknownLabel { line: usize, label: String },
+    /// `IN` on `line` with every input value already read
+    InputExhausted { line: usize },
+    /// `OUT` on `line` wrote `got` where the level expected another value, or none at all
+    WrongOutput { line: usize, got: i32, expected: Option<i32> },
+}
+
+impl RuntimeError {
+    /// line the error happened on
+    pub fn line(&self) -> usize {
+        match self {
+            RuntimeError::IpOutOfBounds { ip } => *ip,
+            RuntimeError::BadJumpTarget { from, .. } => *from,
+            RuntimeError::UnknownLabel { line, .. } => *line,
+            RuntimeError::InputExhausted { line } => *line,
+            RuntimeError::WrongOutput { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::IpOutOfBounds { ip } =>
+                write!(f, "instruction pointer at line {} is outside the program", ip + 1),
+            RuntimeError::BadJumpTarget { from, to } =>
+                write!(f, "jump on line {} to line {} is outside the program", from + 1, to + 1),
+            RuntimeError::UnknownLabel { line, label } =>
+                write!(f, "jump on line {} to unknown label {}", line + 1, label),
+            RuntimeError::InputExhausted { line } =>
+                write!(f, "line {} reads past the end of the input", line + 1),
+            RuntimeError::WrongOutput { line, got, expected: Some(expected) } =>
+                write!(f, "line {} wrote {} but {} was expected", line + 1, got, expected),
+            RuntimeError::WrongOutput { line, got, expected: None } =>
+                write!(f, "line {} wrote {} but nothing was expected", line + 1, got),
+        }
+    }
+}
+
+/// the part of a crab that changes while a program runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CrabState {
+    /// indexed by `Register::index`
+    pub registers: [i32; 5],
+    pub ip: usize,
+    pub pos_y: i32,
+    pub pos_x: i32,
+    pub input_pos: usize,
+    /// values written so far, the output only ever grows so this is enough to restore it
+    pub output_len: usize,
+}
+
+impl CrabState {
+    pub fn get_reg(&self, reg: Register) -> i32 {
+        self.registers[reg.index()]
+    }
+}
+
+/// an instruction decoded for execution
+///
+/// registers are array indices and jumps hold the instruction they land on,
+/// so stepping never allocates or searches the program
+#[derive(Debug, Clone, Copy)]
+enum Instr {
+    /// labels and comments, which do not run the motor
+    Skip,
+    /// also used for writes to `R`, which are ignored
+    Nop,
+    MovI(i32, usize),
+    Mov(usize, usize),
+    AddI(i32, usize),
+    Add(usize, usize),
+    SubI(i32, usize),
+    Sub(usize, usize),
+    Neg(usize),
+    Jmp(usize),
+    Jez(usize),
+    Jnz(usize),
+    Jgz(usize),
+    Jlz(usize),
+    /// jump to a label that does not exist
+    UnknownLabel,
+    /// `JRO` with an immediate that lands outside the program
+    OutOfBounds(i64),
+    Jro(usize),
+    Rcw,
+    Rcc,
+    /// reads the next input value, into nothing for `R`
+    In(Option<usize>),
+    Out(usize),
+    OutI(i32),
+}
+
+const H: usize = Register::H as usize;
+const V: usize = Register::V as usize;
+const A: usize = Register::A as usize;
+const M: usize = Register::M as usize;
+const R: usize = Register::R as usize;
+
+/// destination register, or None when it is `R` and the write is ignored
+fn dest(reg: Register) -> Option<usize> {
+    if reg == Register::R { None } else { Some(reg.index()) }
+}
+
+fn decode(code: &[OpCode], ip: usize) -> Instr {
+    use self::OpCode::*;
+    let label = |lbl: &str, instr: fn(usize) -> Instr| {
+        find_label(code, lbl).map(instr).unwrap_or(Instr::UnknownLabel)
+    };
+    match &code[ip] {
+        LABEL(_) | COMMENT(_) => Instr::Skip,
+        NOP => Instr::Nop,
+        MOVI(num, reg) => dest(*reg).map(|d| Instr::MovI(*num, d)).unwrap_or(Instr::Nop),
+        MOV(src, reg) => dest(*reg).map(|d| Instr::Mov(src.index(), d)).unwrap_or(Instr::Nop),
+        ADDI(num, reg) => dest(*reg).map(|d| Instr::AddI(*num, d)).unwrap_or(Instr::Nop),
+        ADD(src, reg) => dest(*reg).map(|d| Instr::Add(src.index(), d)).unwrap_or(Instr::Nop),
+        SUBI(num, reg) => dest(*reg).map(|d| Instr::SubI(*num, d)).unwrap_or(Instr::Nop),
+        SUB(src, reg) => dest(*reg).map(|d| Instr::Sub(src.index(), d)).unwrap_or(Instr::Nop),
+        NEG(reg) => dest(*reg).map(Instr::Neg).unwrap_or(Instr::Nop),
+        JMP(lbl) => label(lbl, Instr::Jmp),
+        JEZ(lbl) => label(lbl, Instr::Jez),
+        JNZ(lbl) => label(lbl, Instr::Jnz),
+        JGZ(lbl) => label(lbl, Instr::Jgz),
+        JLZ(lbl) => label(lbl, Instr::Jlz),
+        JROI(num) => {
+            let to = ip as i64 + *num as i64;
+            if to < 0 || to >= code.len() as i64 { Instr::OutOfBounds(to) } else { Instr::Jmp(to as usize) }
+        }
+        JRO(reg) => Instr::Jro(reg.index()),
+        RCW => Instr::Rcw,
+        RCC => Instr::Rcc,
+        IN(reg) => Instr::In(dest(*reg)),
+        OUT(reg) => Instr::Out(reg.index()),
+        OUTI(num) => Instr::OutI(*num),
+    }
 }
 
 impl Crab {
     pub fn new() -> Self {
-        let mut registers = HashMap::new();
-        registers.insert(Register::A, 0);
-        registers.insert(Register::M, 0);
-        registers.insert(Register::H, 0);
-        registers.insert(Register::V, 0);
-        registers.insert(Register::R, 0);
         Self {
-            registers,
+            registers: [0; 5],
             ip: 0,
             code: vec![],
+            program: vec![],
             pos_x: INIT_X,
             pos_y: INIT_Y,
+            input: vec![],
+            input_pos: 0,
+            output: vec![],
         }
     }
 
@@ -40,8 +203,8 @@ impl Crab {
             return Err(lines.iter().position(|i|i.is_none()).unwrap());
         }
 
-        let lines = lines.into_iter().map(|i|i.unwrap()).collect();
-        self.code = lines;
+        self.code = lines.into_iter().map(|i|i.unwrap()).collect();
+        self.program = (0..self.code.len()).map(|ip| decode(&self.code, ip)).collect();
         Ok(())
     }
 
@@ -57,229 +220,148 @@ impl Crab {
     /// 2 ^ : the crab's right is (x, y) = (1, 0)
     /// 3 > : the crab's right is (x, y) = (0, 1)
     pub fn dir(&self) -> (i32, i32) {
-        match self.get_reg(Register::R) {
-            0 => (-1, 0),
-            1 => (0, -1),
-            2 => (1, 0),
-            3 => (0, 1),
-            _ => panic!("impossible.")
-        }
-
+        DIRS[self.registers[R] as usize]
     }
 
     pub fn motor(&mut self) {
         if self.stopped() { return; }
-        let m = self.get_reg(Register::M);
-        let to_move = m.signum();
+        let to_move = self.registers[M].signum();
         let (x, y) = self.dir();
         self.pos_x += x * to_move;
         self.pos_y += y * to_move;
     }
 
-    pub fn sensor(&mut self/*, game: &Game*/) {
-        // ...
-    }
-
-    fn find_label(&self, to_find: &str) -> Option<usize> {
-        for (i, op) in self.code.iter().enumerate() {
-            if let OpCode::LABEL(lbl) = op {
-                if lbl == to_find {
-                    return Some(i)
-                }
-            }
-        }
-        None
+    /// steps from the crab to the nearest of `tiles`, or (0, 0) when there are none
+    ///
+    /// distance is counted in steps, ties go to the tile that comes first
+    pub fn nearest(&self, tiles: &[(i32, i32)]) -> (i32, i32) {
+        tiles.iter()
+            .map(|(x, y)| (x - self.pos_x, y - self.pos_y))
+            .min_by_key(|(h, v)| h.abs() + v.abs())
+            .unwrap_or((0, 0))
     }
 
-    pub fn get_reg_mut(&mut self, reg: Register) -> Option<&mut i32> {
-        if reg == Register::R {
-            None
-        } else {
-            self.registers.get_mut(&reg)
-        }
+    /// sets `H` and `V` to what the sensor reads
+    pub fn set_sensor(&mut self, (h, v): (i32, i32)) {
+        self.registers[H] = h;
+        self.registers[V] = v;
     }
 
     pub fn get_reg(&self, reg: Register) -> i32 {
-        *self.registers.get(&reg).unwrap()
+        self.registers[reg.index()]
     }
 
     pub fn reset(&mut self) {
-        for i in self.registers.values_mut() {
-            *i = 0;
-        }
+        self.registers = [0; 5];
         self.pos_x = INIT_X;
         self.pos_y = INIT_Y;
         self.ip = 0;
+        self.input_pos = 0;
+        self.output.clear();
+    }
+
+    pub fn save(&self) -> CrabState {
+        CrabState {
+            registers: self.registers,
+            ip: self.ip,
+            pos_y: self.pos_y,
+            pos_x: self.pos_x,
+            input_pos: self.input_pos,
+            output_len: self.output.len(),
+        }
     }
 
-    pub fn execute(&mut self) -> Result<(), String> {
+    pub fn restore(&mut self, state: &CrabState) {
+        self.registers = state.registers;
+        self.ip = state.ip;
+        self.pos_y = state.pos_y;
+        self.pos_x = state.pos_x;
+        self.input_pos = state.input_pos;
+        self.output.truncate(state.output_len);
+    }
+
+    /// runs until the program ends or `limit` cycles have passed, returns the cycles taken
+    ///
+    /// check `stopped` to tell whether the program ended
+    pub fn execute(&mut self, limit: usize) -> Result<usize, RuntimeError> {
         let mut cycles = 0;
-        loop {
+        while !self.stopped() && cycles < limit {
             cycles += 1;
             self.step()?;
-            if self.ip >= self.code.len() {
-                break;
-            }
-            if cycles > 1_000_000 {
-                return Err("executed more than 1000000 cycles".to_owned())
-            }
         }
-
-        Ok(())
+        Ok(cycles)
     }
 
     pub fn stopped(&self) -> bool {
-        self.ip >= self.code.len()
+        self.ip >= self.program.len()
     }
 
-    pub fn step(&mut self) -> Result<(), String> {
-        if self.stopped() {
-            return Err("Runtime error: instruction pointer OOB".to_owned())
-        }
-
-        dbg!(&self);
-        dbg!(&self.code[self.ip]);
-
-        let op = self.code[self.ip].clone();
-
-        match &op {
-            LABEL(_) => (),
-            COMMENT(_) => (),
-            _ => self.motor(),
+    fn unknown_label(&self) -> RuntimeError {
+        use self::OpCode::*;
+        let label = match &self.code[self.ip] {
+            JMP(lbl) | JEZ(lbl) | JNZ(lbl) | JGZ(lbl) | JLZ(lbl) => lbl.clone(),
+            _ => unreachable!(),
         };
+        RuntimeError::UnknownLabel { line: self.ip, label }
+    }
 
-        use self::OpCode::*;
-        match op {
-            NOP => {
-                self.ip += 1;
-            },
-            LABEL(lbl) => {
-                self.ip += 1;
-                return Ok(());
-            },
-            COMMENT(_) => {
-                self.ip += 1;
-            },
-            MOVI(num, reg) => {
-                if let Some(reg) = self.get_reg_mut(reg) {
-                    *reg = num;
-                }
-                self.ip += 1;
-            }
-            MOV(reg1, reg2) => {
-                let val = self.get_reg(reg1);
-                if let Some(reg) = self.get_reg_mut(reg2) {
-                    *reg = val;
-                }
-                self.ip += 1;
-            }
-            ADDI(num, reg) => {
-                if let Some(reg) = self.get_reg_mut(reg) {
-                    *reg += num;
-                }
-                self.ip += 1;
-            }
-            ADD(reg1, reg2) => {
-                let val = self.get_reg(reg1);
-                if let Some(reg) = self.get_reg_mut(reg2) {
-                    *reg += val;
-                }
-                self.ip += 1;
-            }
-            SUBI(num, reg) => {
-                if let Some(reg) = self.get_reg_mut(reg) {
-                    *reg -= num;
-                }
-                self.ip += 1;
-            }
-            SUB(reg1, reg2) => {
-                let val = self.get_reg(reg1);
-                if let Some(reg) = self.get_reg_mut(reg2) {
-                    *reg -= val;
-                }
-                self.ip += 1;
-            }
-            NEG(reg) => {
-                let val = -self.get_reg(reg);
-                if let Some(reg) = self.get_reg_mut(reg) {
-                    *reg = val;
-                }
-                self.ip += 1;
-            }
-            JMP(lbl) => {
-                let loc = self.find_label(&lbl).ok_or(format!("Cannot find label. {}", lbl))?;
-                dbg!(loc);
-                self.ip = loc;
-            }
-            JEZ(lbl) => {
-                let loc = self.find_label(&lbl).ok_or(format!("Cannot find label. {}", lbl))?;
-                if self.get_reg(Register::A) == 0 {
-                    self.ip = loc;
-                } else {
-                    self.ip += 1;
-                }
-            }
-            JNZ(lbl) => {
-                let loc = self.find_label(&lbl).ok_or(format!("Cannot find label. {}", lbl))?;
-                if self.get_reg(Register::A) != 0 {
-                    self.ip = loc;
-                } else {
-                    self.ip += 1;
-                }
-            }
-            JGZ(lbl) => {
-                let loc = self.find_label(&lbl).ok_or(format!("Cannot find label. {}", lbl))?;
-                if self.get_reg(Register::A) > 0 {
-                    self.ip = loc;
-                } else {
-                    self.ip += 1;
-                }
-            }
-            // jump to label if acc < 0
-            JLZ(lbl) => {
-                let loc = self.find_label(&lbl).ok_or(format!("Cannot find label. {}", lbl))?;
-                if self.get_reg(Register::A) < 0 {
-                    self.ip = loc;
-                } else {
-                    self.ip += 1;
-                }
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        let instr = match self.program.get(self.ip) {
+            Some(instr) => *instr,
+            None => return Err(RuntimeError::IpOutOfBounds { ip: self.ip }),
+        };
+        if let Instr::Skip = instr {
+            self.ip += 1;
+            return Ok(());
+        }
+        self.motor();
 
-            }
-            // unconditional relative jump
-            JROI(num) => {
-                let tmp = self.ip as i32 + num;
-                if (tmp < 0) || (self.ip >= self.code.len()) {
-                    return Err("Cannot jump to that location".to_owned())
-                } else {
-                    self.ip = tmp as usize;
+        let next = self.ip + 1;
+        let r = &mut self.registers;
+        self.ip = match instr {
+            Instr::Skip | Instr::Nop => next,
+            Instr::MovI(num, d) => { r[d] = num; next }
+            Instr::Mov(s, d) => { r[d] = r[s]; next }
+            Instr::AddI(num, d) => { r[d] += num; next }
+            Instr::Add(s, d) => { r[d] += r[s]; next }
+            Instr::SubI(num, d) => { r[d] -= num; next }
+            Instr::Sub(s, d) => { r[d] -= r[s]; next }
+            Instr::Neg(d) => { r[d] = -r[d]; next }
+            Instr::Jmp(to) => to,
+            Instr::Jez(to) => if r[A] == 0 { to } else { next },
+            Instr::Jnz(to) => if r[A] != 0 { to } else { next },
+            Instr::Jgz(to) => if r[A] > 0 { to } else { next },
+            Instr::Jlz(to) => if r[A] < 0 { to } else { next },
+            Instr::UnknownLabel => return Err(self.unknown_label()),
+            Instr::OutOfBounds(to) => return Err(RuntimeError::BadJumpTarget { from: self.ip, to }),
+            Instr::Jro(s) => {
+                let to = self.ip as i64 + r[s] as i64;
+                if to < 0 || to >= self.program.len() as i64 {
+                    return Err(RuntimeError::BadJumpTarget { from: self.ip, to });
                 }
+                to as usize
             }
-            // unconditional relative jump with value from register
-            JRO(reg) => {
-                let num = self.get_reg(reg);
-                let tmp = self.ip as i32 + num;
-                if (tmp < 0) || (self.ip >= self.code.len()) {
-                    return Err("Cannot jump to that location".to_owned())
-                } else {
-                    self.ip = tmp as usize;
+            Instr::Rcw => { r[R] = (r[R] + 1) % 4; next }
+            Instr::Rcc => { r[R] = (r[R] + 3) % 4; next }
+            Instr::In(d) => {
+                let val = match self.input.get(self.input_pos) {
+                    Some(val) => *val,
+                    None => return Err(RuntimeError::InputExhausted { line: self.ip }),
+                };
+                self.input_pos += 1;
+                if let Some(d) = d {
+                    r[d] = val;
                 }
+                next
             }
-            RCW => {
-                let dir = self.registers.get_mut(&Register::R).unwrap();
-                *dir = (*dir + 1) % 4;
-                self.ip += 1;
-            }
-            RCC => {
-                let dir = self.registers.get_mut(&Register::R).unwrap();
-                *dir = (*dir + 3) % 4;
-                self.ip += 1;
-            }
+            Instr::Out(s) => { self.output.push(r[s]); next }
+            Instr::OutI(num) => { self.output.push(num); next }
         };
         Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum OpCode {
     NOP,
     LABEL(String),
@@ -307,95 +389,108 @@ pub enum OpCode {
     JRO(Register),
     /// rotate clockwise
     RCW,
-    /// rotate counterclosewise
+    /// rotate counterclockwise
     RCC,
+    /// read the next input value
+    IN(Register),
+    /// write a register to the output
+    OUT(Register),
+    /// write a number to the output
+    OUTI(i32),
 }
 
 impl FromStr for OpCode {
     type Err = String;
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        let tokens: Vec<_> = line.split_whitespace().collect();
-        let code = tokens.get(0).ok_or("does not exist".to_owned())?;
-        let op1 = tokens.get(1).ok_or("does not exist".to_owned());
-        let op2 = tokens.get(2).ok_or("does not exist".to_owned());
-
-        match code {
-            &"MOV" => {
-                if op1.clone()?.chars().all(|i|i.is_digit(10) || i == '-') {
-                    let op1 = op1?.parse().map_err(|_| "cannot parse int".to_owned())?;
-                    let op2 = op2?.parse()?;
-                    Ok(OpCode::MOVI(op1, op2))
-                } else {
-                    let op1 = op1?.parse()?;
-                    let op2 = op2?.parse()?;
-                    Ok(OpCode::MOV(op1, op2))
-                }
-            }
-            &"ADD" => {
-                if op1.clone()?.chars().all(|i|i.is_digit(10) || i == '-') {
-                    let op1 = op1?.parse().map_err(|_| "cannot parse int".to_owned())?;
-                    let op2 = op2?.parse()?;
-                    Ok(OpCode::ADDI(op1, op2))
-                } else {
-                    let op1 = op1?.parse()?;
-                    let op2 = op2?.parse()?;
-                    Ok(OpCode::ADD(op1, op2))
-                }
-            }
-            &"SUB" => {
-                if op1.clone()?.chars().all(|i|i.is_digit(10) || i == '-') {
-                    let op1 = op1?.parse().map_err(|_| "cannot parse int".to_owned())?;
-                    let op2 = op2?.parse()?;
-                    Ok(OpCode::SUBI(op1, op2))
-                } else {
-                    let op1 = op1?.parse()?;
-                    let op2 = op2?.parse()?;
-                    Ok(OpCode::SUB(op1, op2))
-                }
-            }
-            &"NEG" => {
-                let op1 = op1?.parse()?;
-                Ok(OpCode::NEG(op1))
-            }
-            &"NOP" => {
-                Ok(OpCode::NOP)
-            }
-            &"JRO" => {
-                if op1.clone()?.chars().all(|i|i.is_digit(10) || i == '-') {
-                    let op1 = op1?.parse().map_err(|_| "cannot parse int".to_owned())?;
-                    Ok(OpCode::JROI(op1))
-                } else {
-                    let op1 = op1?.parse()?;
-                    Ok(OpCode::JRO(op1))
-                }
-            }
-            &"JMP" => Ok(OpCode::JMP(op1?.to_string())),
-            &"JEZ" => Ok(OpCode::JEZ(op1?.to_string())),
-            &"JNZ" => Ok(OpCode::JNZ(op1?.to_string())),
-            &"JGZ" => Ok(OpCode::JGZ(op1?.to_string())),
-            &"JLZ" => Ok(OpCode::JLZ(op1?.to_string())),
-            &"RCW" => Ok(OpCode::RCW),
-            &"RCC" => Ok(OpCode::RCC),
-            _ => {
-                if code.starts_with("#") {
-                    Ok(OpCode::COMMENT( line.to_string()) )
-                } else if code.ends_with(":") {
-                    Ok(OpCode::LABEL( code[0..code.len()-1].to_string() ))
-                } else {
-                    Err("Not a valid instruction!".to_owned())
-                }
+        parse_line(line).map_err(|e| e.message)
+    }
+}
+
+impl OpCode {
+    /// builds an instruction from operands that match `isa::find(mnemonic)`, None if they don't
+    pub fn from_parts(mnemonic: &str, args: &[Arg]) -> Option<OpCode> {
+        use self::Arg::*;
+        use self::OpCode::*;
+        Some(match (mnemonic, args) {
+            ("NOP", []) => NOP,
+            ("RCW", []) => RCW,
+            ("RCC", []) => RCC,
+            ("MOV", [Num(num), Reg(dst)]) => MOVI(*num, *dst),
+            ("MOV", [Reg(src), Reg(dst)]) => MOV(*src, *dst),
+            ("ADD", [Num(num), Reg(dst)]) => ADDI(*num, *dst),
+            ("ADD", [Reg(src), Reg(dst)]) => ADD(*src, *dst),
+            ("SUB", [Num(num), Reg(dst)]) => SUBI(*num, *dst),
+            ("SUB", [Reg(src), Reg(dst)]) => SUB(*src, *dst),
+            ("NEG", [Reg(reg)]) => NEG(*reg),
+            ("JMP", [Label(label)]) => JMP(label.clone()),
+            ("JEZ", [Label(label)]) => JEZ(label.clone()),
+            ("JNZ", [Label(label)]) => JNZ(label.clone()),
+            ("JGZ", [Label(label)]) => JGZ(label.clone()),
+            ("JLZ", [Label(label)]) => JLZ(label.clone()),
+            ("JRO", [Num(num)]) => JROI(*num),
+            ("JRO", [Reg(reg)]) => JRO(*reg),
+            ("IN", [Reg(reg)]) => IN(*reg),
+            ("OUT", [Num(num)]) => OUTI(*num),
+            ("OUT", [Reg(reg)]) => OUT(*reg),
+            _ => return None,
+        })
+    }
+
+    /// the mnemonic and operands as written, None for labels and comments
+    pub fn parts(&self) -> Option<(&'static str, Vec<Arg>)> {
+        use self::Arg::*;
+        use self::OpCode::*;
+        Some(match self {
+            NOP => ("NOP", vec![]),
+            RCW => ("RCW", vec![]),
+            RCC => ("RCC", vec![]),
+            MOVI(num, dst) => ("MOV", vec![Num(*num), Reg(*dst)]),
+            MOV(src, dst) => ("MOV", vec![Reg(*src), Reg(*dst)]),
+            ADDI(num, dst) => ("ADD", vec![Num(*num), Reg(*dst)]),
+            ADD(src, dst) => ("ADD", vec![Reg(*src), Reg(*dst)]),
+            SUBI(num, dst) => ("SUB", vec![Num(*num), Reg(*dst)]),
+            SUB(src, dst) => ("SUB", vec![Reg(*src), Reg(*dst)]),
+            NEG(reg) => ("NEG", vec![Reg(*reg)]),
+            JMP(label) => ("JMP", vec![Label(label.clone())]),
+            JEZ(label) => ("JEZ", vec![Label(label.clone())]),
+            JNZ(label) => ("JNZ", vec![Label(label.clone())]),
+            JGZ(label) => ("JGZ", vec![Label(label.clone())]),
+            JLZ(label) => ("JLZ", vec![Label(label.clone())]),
+            JROI(num) => ("JRO", vec![Num(*num)]),
+            JRO(reg) => ("JRO", vec![Reg(*reg)]),
+            IN(reg) => ("IN", vec![Reg(*reg)]),
+            OUTI(num) => ("OUT", vec![Num(*num)]),
+            OUT(reg) => ("OUT", vec![Reg(*reg)]),
+            LABEL(_) | COMMENT(_) => return None,
+        })
+    }
+}
+
+/// the line as it would be written in a program
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpCode::LABEL(label) => write!(f, "{}:", label),
+            OpCode::COMMENT(text) => write!(f, "{}", text),
+            op => {
+                let (mnemonic, args) = op.parts().unwrap();
+                write!(f, "{}", mnemonic)?;
+                args.iter().try_for_each(|arg| write!(f, " {}", arg))
             }
         }
     }
 }
 
 
+/// every register, in the order they are displayed
+pub const REGISTERS: [Register; 5] = [Register::A, Register::M, Register::H, Register::V, Register::R];
+
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
 pub enum Register {
-    /// object distance in horizontal direction
+    /// object distance in horizontal direction, set by the sensor every cycle
     H,
-    /// object distance in vertical direction
+    /// object distance in vertical direction, set by the sensor every cycle
     V,
     /// general purpose register
     A,
@@ -405,6 +500,13 @@ pub enum Register {
     R,
 }
 
+impl Register {
+    /// position of the register in `Crab::registers`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
 impl FromStr for Register {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -453,7 +555,7 @@ mod tests {
                 JGZ LABEL
         "#;
         crab.load_code(code).unwrap();
-        crab.execute().unwrap();
+        crab.execute(1_000_000).unwrap();
         // crab.step().unwrap();
         // crab.step().unwrap();
         // crab.step().unwrap();
@@ -468,4 +570,43 @@ mod tests {
         // crab.step().unwrap();
         // crab.step().unwrap();
     }
+
+    #[test]
+    fn test_decoded() {
+        let mut crab = Crab::new();
+        crab.load_code("MOV 2 A\nMOV 1 R\nADD A R\nL:\nRCC\nSUB 1 A\nJGZ L\nNEG A\nMOV A H").unwrap();
+        assert_eq!(crab.execute(100), Ok(13));
+        // writes to R are ignored, RCC twice from 0 leaves it at 2
+        assert_eq!(crab.get_reg(Register::R), 2);
+        assert_eq!(crab.save().registers, [0, 0, 0, 0, 2]);
+        let state = crab.save();
+        crab.reset();
+        crab.restore(&state);
+        assert_eq!(crab.save(), state);
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let mut crab = Crab::new();
+        crab.load_code("NOP\nJRO 1").unwrap();
+        crab.step().unwrap();
+        // jumping to just past the end is still out of bounds
+        assert_eq!(crab.step(), Err(RuntimeError::BadJumpTarget { from: 1, to: 2 }));
+
+        let mut crab = Crab::new();
+        crab.load_code("JMP NOWHERE").unwrap();
+        assert_eq!(crab.execute(10), Err(RuntimeError::UnknownLabel { line: 0, label: "NOWHERE".to_owned() }));
+
+        let mut crab = Crab::new();
+        crab.load_code("NOP").unwrap();
+        assert_eq!(crab.execute(10), Ok(1));
+        assert_eq!(crab.step(), Err(RuntimeError::IpOutOfBounds { ip: 1 }));
+
+        let mut crab = Crab::new();
+        crab.load_code("IN A\nOUT A\nOUT -1\nIN R\nIN A").unwrap();
+        crab.input = vec![5, 6];
+        assert_eq!(crab.execute(10), Err(RuntimeError::InputExhausted { line: 4 }));
+        assert_eq!(crab.output, vec![5, -1]);
+        assert_eq!(crab.get_reg(Register::R), 0);
+    }
 }
@@ -0,0 +1,206 @@
+use crate::crab::{Crab, CrabState, Register};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// value that can show up on either side of a breakpoint condition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Reg(Register),
+    Cycle,
+    PosX,
+    PosY,
+    Num(i32),
+}
+
+impl Operand {
+    fn eval(&self, crab: &Crab, cycle: usize) -> i32 {
+        match self {
+            Operand::Reg(reg) => crab.get_reg(*reg),
+            Operand::Cycle => cycle as i32,
+            Operand::PosX => crab.pos_x,
+            Operand::PosY => crab.pos_y,
+            Operand::Num(n) => *n,
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "CYCLE" => Ok(Operand::Cycle),
+            "X" => Ok(Operand::PosX),
+            "Y" => Ok(Operand::PosY),
+            s => {
+                if let Ok(reg) = s.parse() {
+                    Ok(Operand::Reg(reg))
+                } else {
+                    s.parse().map(Operand::Num).map_err(|_| format!("unknown operand {}", s))
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// comparison such as `A == 3` or `CYCLE > 50`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub lhs: Operand,
+    pub cmp: Cmp,
+    pub rhs: Operand,
+}
+
+impl Condition {
+    pub fn eval(&self, crab: &Crab, cycle: usize) -> bool {
+        let lhs = self.lhs.eval(crab, cycle);
+        let rhs = self.rhs.eval(crab, cycle);
+        match self.cmp {
+            Cmp::Eq => lhs == rhs,
+            Cmp::Ne => lhs != rhs,
+            Cmp::Lt => lhs < rhs,
+            Cmp::Le => lhs <= rhs,
+            Cmp::Gt => lhs > rhs,
+            Cmp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // two character operators go first so `<=` is not read as `<`
+        let ops = [
+            ("==", Cmp::Eq), ("!=", Cmp::Ne), ("<=", Cmp::Le), (">=", Cmp::Ge),
+            ("<", Cmp::Lt), (">", Cmp::Gt), ("=", Cmp::Eq),
+        ];
+        for (sym, cmp) in ops.iter() {
+            if let Some(idx) = s.find(sym) {
+                let lhs = s[..idx].trim().parse()?;
+                let rhs = s[idx + sym.len()..].trim().parse()?;
+                return Ok(Condition { lhs, cmp: *cmp, rhs });
+            }
+        }
+        Err("expected a comparison".to_owned())
+    }
+}
+
+/// value that pauses the program whenever it changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Watch {
+    Reg(Register),
+    Position,
+}
+
+impl FromStr for Watch {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "POS" => Ok(Watch::Position),
+            s => s.parse().map(Watch::Reg).map_err(|_| format!("cannot watch {}", s)),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    /// breakpoints by line, with an optional condition
+    pub breakpoints: BTreeMap<usize, Option<Condition>>,
+    pub watches: Vec<Watch>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn toggle_breakpoint(&mut self, line: usize) {
+        if self.breakpoints.remove(&line).is_none() {
+            self.breakpoints.insert(line, None);
+        }
+    }
+
+    /// sets the condition of the breakpoint on `line`, adding the breakpoint if needed
+    pub fn set_condition(&mut self, line: usize, condition: Option<Condition>) {
+        self.breakpoints.insert(line, condition);
+    }
+
+    pub fn toggle_watch(&mut self, watch: Watch) {
+        if let Some(idx) = self.watches.iter().position(|w| *w == watch) {
+            self.watches.remove(idx);
+        } else {
+            self.watches.push(watch);
+        }
+    }
+
+    /// checks the state after a cycle, `before` is the state the cycle started from
+    ///
+    /// returns the reason to pause, if any
+    pub fn check(&self, before: &CrabState, crab: &Crab, cycle: usize) -> Option<String> {
+        for watch in &self.watches {
+            match watch {
+                Watch::Reg(reg) => {
+                    let old = before.get_reg(*reg);
+                    let new = crab.get_reg(*reg);
+                    if old != new {
+                        return Some(format!("WATCH {:?}: {} -> {}", reg, old, new));
+                    }
+                }
+                Watch::Position => {
+                    if (before.pos_x, before.pos_y) != (crab.pos_x, crab.pos_y) {
+                        return Some(format!("WATCH POS: {},{}", crab.pos_x, crab.pos_y));
+                    }
+                }
+            }
+        }
+        match self.breakpoints.get(&crab.ip) {
+            Some(None) => Some(format!("BREAK LINE {}", crab.ip + 1)),
+            Some(Some(cond)) if cond.eval(crab, cycle) => Some(format!("BREAK LINE {}", crab.ip + 1)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_condition() {
+        let cond: Condition = "A == 3".parse().unwrap();
+        assert_eq!(cond, Condition { lhs: Operand::Reg(Register::A), cmp: Cmp::Eq, rhs: Operand::Num(3) });
+        let cond: Condition = "CYCLE>=-2".parse().unwrap();
+        assert_eq!(cond, Condition { lhs: Operand::Cycle, cmp: Cmp::Ge, rhs: Operand::Num(-2) });
+        assert!("A".parse::<Condition>().is_err());
+        assert!("Q < 1".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let mut crab = Crab::new();
+        crab.load_code("MOV 1 A\nNOP\nNOP").unwrap();
+        let mut debugger = Debugger::new();
+        debugger.set_condition(2, Some("A == 2".parse().unwrap()));
+        debugger.toggle_watch(Watch::Reg(Register::A));
+
+        let before = crab.save();
+        crab.step().unwrap();
+        assert_eq!(debugger.check(&before, &crab, 1), Some("WATCH A: 0 -> 1".to_owned()));
+
+        let before = crab.save();
+        crab.step().unwrap();
+        assert_eq!(debugger.check(&before, &crab, 2), None);
+
+        debugger.set_condition(2, Some("A == 1".parse().unwrap()));
+        assert_eq!(debugger.check(&before, &crab, 2), Some("BREAK LINE 3".to_owned()));
+    }
+}
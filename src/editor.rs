@@ -0,0 +1,605 @@
+//! the text of the code editor with a cursor, selection, clipboard and undo history
+
+/// edits kept for undo
+pub const UNDO_LEN: usize = 100;
+
+/// a place between two characters, line and column count from 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Pos {
+    pub fn new(line: usize, col: usize) -> Self {
+        Pos { line, col }
+    }
+
+    /// the first line and column of a `view` sized window moved as little as it takes to show `pos`
+    pub fn scroll_to(self, pos: Pos, view: Pos) -> Pos {
+        Pos {
+            line: self.line.min(pos.line).max((pos.line + 1).saturating_sub(view.line)),
+            // the cursor can sit just after the last character, which needs a column of its own
+            col: self.col.min(pos.col).max((pos.col + 1).saturating_sub(view.col)),
+        }
+    }
+}
+
+/// commands bound to keys, the moves extend the selection when shift is held
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Top,
+    Bottom,
+    Backspace,
+    Delete,
+    SelectAll,
+    Copy,
+    Cut,
+    Paste,
+    Undo,
+    Redo,
+}
+
+impl Edit {
+    /// false for the commands that leave the text alone
+    pub fn modifies(self) -> bool {
+        matches!(self, Edit::Backspace | Edit::Delete | Edit::Cut | Edit::Paste | Edit::Undo | Edit::Redo)
+    }
+}
+
+/// what kept an edit from being made
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Lines,
+    Width,
+}
+
+/// the text and cursor before an edit
+#[derive(Debug, Clone)]
+struct Snapshot {
+    lines: Vec<String>,
+    cursor: Pos,
+}
+
+pub struct TextBuffer {
+    /// never empty, an empty buffer is one empty line
+    lines: Vec<String>,
+    cursor: Pos,
+    /// the end of the selection that stays put while the cursor moves
+    anchor: Option<Pos>,
+    /// column up and down try to return to
+    goal_col: Option<usize>,
+    clipboard: String,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// set while typing a word, which is undone as a whole
+    typing: bool,
+    /// the limit the last edit ran into
+    blocked: Option<Limit>,
+    pub max_lines: usize,
+    /// characters a line can hold
+    pub max_len: usize,
+}
+
+impl TextBuffer {
+    pub fn new(max_lines: usize, max_len: usize) -> Self {
+        TextBuffer {
+            lines: vec![String::new()],
+            cursor: Pos::new(0, 0),
+            anchor: None,
+            goal_col: None,
+            clipboard: String::new(),
+            undo: vec![],
+            redo: vec![],
+            typing: false,
+            blocked: None,
+            max_lines,
+            max_len,
+        }
+    }
+
+    /// runs a command, returns false if an edit had nothing to act on or a limit blocked it
+    pub fn apply(&mut self, edit: Edit, select: bool) -> bool {
+        self.blocked = None;
+        match edit {
+            Edit::Left => self.move_left(select),
+            Edit::Right => self.move_right(select),
+            Edit::Up => self.move_up(select),
+            Edit::Down => self.move_down(select),
+            Edit::Home => self.home(select),
+            Edit::End => self.end(select),
+            Edit::Top => self.start_of_buffer(select),
+            Edit::Bottom => self.end_of_buffer(select),
+            Edit::SelectAll => self.select_all(),
+            Edit::Copy => self.copy(),
+            Edit::Backspace => return self.backspace(),
+            Edit::Delete => return self.delete(),
+            Edit::Cut => return self.cut(),
+            Edit::Paste => return self.paste(),
+            Edit::Undo => return self.undo(),
+            Edit::Redo => return self.redo(),
+        }
+        true
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn cursor(&self) -> Pos {
+        self.cursor
+    }
+
+    /// why the last edit was refused, None if it was made or had nothing to act on
+    pub fn blocked(&self) -> Option<Limit> {
+        self.blocked
+    }
+
+    /// replaces the whole text and puts the cursor at its end, can be undone
+    /// replaces the whole text, characters that are not ASCII become `?` since columns are bytes
+    pub fn set_text(&mut self, text: &str) {
+        self.checkpoint();
+        self.lines = split_lines(text);
+        self.anchor = None;
+        self.end_of_buffer(false);
+    }
+
+    /// start and end of the selection, None if nothing is selected
+    pub fn selection(&self) -> Option<(Pos, Pos)> {
+        let anchor = self.anchor?;
+        if anchor == self.cursor {
+            return None;
+        }
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection()?;
+        if start.line == end.line {
+            return Some(self.lines[start.line][start.col..end.col].to_owned());
+        }
+        let mut text = self.lines[start.line][start.col..].to_owned();
+        for line in &self.lines[start.line + 1..end.line] {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text.push('\n');
+        text.push_str(&self.lines[end.line][..end.col]);
+        Some(text)
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(Pos::new(0, 0));
+        self.end_of_buffer(true);
+    }
+
+    /// starts or drops the selection before the cursor moves
+    fn start_move(&mut self, select: bool) {
+        self.typing = false;
+        if !select {
+            self.anchor = None;
+        } else if self.anchor.is_none() {
+            self.anchor = Some(self.cursor);
+        }
+    }
+
+    fn line_len(&self, line: usize) -> usize {
+        self.lines[line].len()
+    }
+
+    pub fn move_left(&mut self, select: bool) {
+        // without shift a selection collapses to its start
+        if let (false, Some((start, _))) = (select, self.selection()) {
+            self.start_move(false);
+            self.cursor = start;
+        } else {
+            self.start_move(select);
+            if self.cursor.col > 0 {
+                self.cursor.col -= 1;
+            } else if self.cursor.line > 0 {
+                self.cursor.line -= 1;
+                self.cursor.col = self.line_len(self.cursor.line);
+            }
+        }
+        self.goal_col = None;
+    }
+
+    pub fn move_right(&mut self, select: bool) {
+        if let (false, Some((_, end))) = (select, self.selection()) {
+            self.start_move(false);
+            self.cursor = end;
+        } else {
+            self.start_move(select);
+            if self.cursor.col < self.line_len(self.cursor.line) {
+                self.cursor.col += 1;
+            } else if self.cursor.line + 1 < self.lines.len() {
+                self.cursor.line += 1;
+                self.cursor.col = 0;
+            }
+        }
+        self.goal_col = None;
+    }
+
+    pub fn move_up(&mut self, select: bool) {
+        self.start_move(select);
+        if self.cursor.line == 0 {
+            self.home(select);
+            return;
+        }
+        self.move_to_line(self.cursor.line - 1);
+    }
+
+    pub fn move_down(&mut self, select: bool) {
+        self.start_move(select);
+        if self.cursor.line + 1 == self.lines.len() {
+            self.end(select);
+            return;
+        }
+        self.move_to_line(self.cursor.line + 1);
+    }
+
+    /// keeps the column of the line the cursor came from, as far as the new line allows
+    fn move_to_line(&mut self, line: usize) {
+        let goal = *self.goal_col.get_or_insert(self.cursor.col);
+        self.cursor = Pos::new(line, goal.min(self.line_len(line)));
+    }
+
+    pub fn home(&mut self, select: bool) {
+        self.start_move(select);
+        self.cursor.col = 0;
+        self.goal_col = None;
+    }
+
+    pub fn end(&mut self, select: bool) {
+        self.start_move(select);
+        self.cursor.col = self.line_len(self.cursor.line);
+        self.goal_col = None;
+    }
+
+    pub fn start_of_buffer(&mut self, select: bool) {
+        self.start_move(select);
+        self.cursor = Pos::new(0, 0);
+        self.goal_col = None;
+    }
+
+    pub fn end_of_buffer(&mut self, select: bool) {
+        self.start_move(select);
+        let line = self.lines.len() - 1;
+        self.cursor = Pos::new(line, self.line_len(line));
+        self.goal_col = None;
+    }
+
+    /// puts the cursor at `pos`, or as close to it as the text allows
+    pub fn move_to(&mut self, pos: Pos, select: bool) {
+        self.start_move(select);
+        let line = pos.line.min(self.lines.len() - 1);
+        self.cursor = Pos::new(line, pos.col.min(self.line_len(line)));
+        self.goal_col = None;
+    }
+
+    /// selects from `start` to `end`, leaving the cursor at `end`
+    pub fn select(&mut self, start: Pos, end: Pos) {
+        self.move_to(start, false);
+        self.move_to(end, true);
+    }
+
+    /// saves the text for undo, dropping what could be redone
+    fn checkpoint(&mut self) {
+        self.typing = false;
+        self.redo.clear();
+        self.undo.push(Snapshot { lines: self.lines.clone(), cursor: self.cursor });
+        if self.undo.len() > UNDO_LEN {
+            self.undo.remove(0);
+        }
+    }
+
+    /// the lines after replacing the selection with `text`
+    ///
+    /// edits that break a limit are refused unless the text already broke it
+    /// further, so a buffer over the limits can still be shortened
+    fn replaced(&self, text: &str) -> Result<(Vec<String>, Pos), Limit> {
+        let (start, end) = self.selection().unwrap_or((self.cursor, self.cursor));
+        let before = &self.lines[start.line][..start.col];
+        let after = &self.lines[end.line][end.col..];
+        let mut new = split_lines(text);
+        new[0].insert_str(0, before);
+        let last = new.len() - 1;
+        let cursor = Pos::new(start.line + last, new[last].len());
+        new[last].push_str(after);
+
+        let count = self.lines.len() - (end.line - start.line) + new.len() - 1;
+        if count > self.max_lines && count > self.lines.len() {
+            return Err(Limit::Lines);
+        }
+        let widest = |lines: &[String]| lines.iter().map(|l| l.len()).max().unwrap_or(0);
+        let width = widest(&new);
+        if width > self.max_len && width > widest(&self.lines[start.line..=end.line]) {
+            return Err(Limit::Width);
+        }
+        let mut lines = self.lines[..start.line].to_vec();
+        lines.extend(new);
+        lines.extend_from_slice(&self.lines[end.line + 1..]);
+        Ok((lines, cursor))
+    }
+
+    /// true if `text` can replace the selection, otherwise remembers the limit in the way
+    fn fits(&mut self, text: &str) -> bool {
+        self.blocked = self.replaced(text).err();
+        self.blocked.is_none()
+    }
+
+    /// replaces the selection, which has to fit
+    fn replace(&mut self, text: &str) {
+        let (lines, cursor) = self.replaced(text).unwrap();
+        self.lines = lines;
+        self.cursor = cursor;
+        self.anchor = None;
+        self.goal_col = None;
+    }
+
+    /// types a character over the selection, returns false if a limit blocked it
+    pub fn insert(&mut self, c: char) -> bool {
+        self.blocked = None;
+        // columns are bytes, so only ASCII is allowed
+        if !c.is_ascii() || (c.is_control() && c != '\n') {
+            return false;
+        }
+        let text = c.to_string();
+        if !self.fits(&text) {
+            return false;
+        }
+        let word = c.is_alphanumeric() || c == '_' || c == '-';
+        // a word typed in one go is undone at once
+        if !(word && self.typing && self.selection().is_none()) {
+            self.checkpoint();
+        }
+        self.replace(&text);
+        self.typing = word;
+        true
+    }
+
+    /// inserts text over the selection, returns false if it would not fit
+    pub fn insert_str(&mut self, text: &str) -> bool {
+        if !self.fits(text) {
+            return false;
+        }
+        self.checkpoint();
+        self.replace(text);
+        true
+    }
+
+    /// deletes the selection, or the character before the cursor
+    pub fn backspace(&mut self) -> bool {
+        let cursor = self.cursor;
+        if self.selection().is_none() {
+            if self.cursor == Pos::new(0, 0) {
+                return false;
+            }
+            self.anchor = Some(self.cursor);
+            self.move_left(true);
+        }
+        self.delete_selection(cursor)
+    }
+
+    /// deletes the selection, or the character after the cursor
+    pub fn delete(&mut self) -> bool {
+        let cursor = self.cursor;
+        if self.selection().is_none() {
+            let line = self.lines.len() - 1;
+            if self.cursor == Pos::new(line, self.line_len(line)) {
+                return false;
+            }
+            self.anchor = Some(self.cursor);
+            self.move_right(true);
+        }
+        self.delete_selection(cursor)
+    }
+
+    /// joining two lines can make one too long, in which case nothing is deleted and the cursor goes back
+    fn delete_selection(&mut self, cursor: Pos) -> bool {
+        if !self.fits("") {
+            self.anchor = None;
+            self.cursor = cursor;
+            return false;
+        }
+        self.checkpoint();
+        self.replace("");
+        true
+    }
+
+    pub fn copy(&mut self) {
+        if let Some(text) = self.selected_text() {
+            self.clipboard = text;
+        }
+    }
+
+    pub fn cut(&mut self) -> bool {
+        match self.selected_text() {
+            Some(text) => {
+                self.clipboard = text;
+                self.delete_selection(self.cursor)
+            }
+            None => false,
+        }
+    }
+
+    pub fn paste(&mut self) -> bool {
+        let text = self.clipboard.clone();
+        !text.is_empty() && self.insert_str(&text)
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.restore(true)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.restore(false)
+    }
+
+    /// moves one snapshot from the undo list to the redo list or back
+    fn restore(&mut self, undo: bool) -> bool {
+        let (from, to) = if undo { (&mut self.undo, &mut self.redo) } else { (&mut self.redo, &mut self.undo) };
+        let snapshot = match from.pop() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        to.push(Snapshot { lines: std::mem::replace(&mut self.lines, snapshot.lines), cursor: self.cursor });
+        self.cursor = snapshot.cursor;
+        self.anchor = None;
+        self.goal_col = None;
+        self.typing = false;
+        true
+    }
+}
+
+/// the lines of `text`, with anything that is not ASCII replaced by `?`
+fn split_lines(text: &str) -> Vec<String> {
+    text.split('\n')
+        .map(|l| l.trim_end_matches('\r').chars().map(|c| if c.is_ascii() { c } else { '?' }).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> TextBuffer {
+        let mut buf = TextBuffer::new(24, 16);
+        buf.set_text(text);
+        buf
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut buf = TextBuffer::new(3, 8);
+        for c in "LOOP_1:\nJMP LOOP_1".chars() {
+            buf.insert(c);
+        }
+        // the last line is too long for 8 characters
+        assert_eq!(buf.text(), "LOOP_1:\nJMP LOOP");
+        assert_eq!(buf.blocked(), Some(Limit::Width));
+        buf.move_up(false);
+        assert_eq!(buf.cursor(), Pos::new(0, 7));
+        buf.home(false);
+        buf.delete();
+        assert_eq!(buf.lines()[0], "OOP_1:");
+        // joining the lines would make one too long
+        buf.end(false);
+        assert!(!buf.delete());
+        assert_eq!(buf.text(), "OOP_1:\nJMP LOOP");
+        assert_eq!(buf.cursor(), Pos::new(0, 6));
+        buf.move_down(false);
+        buf.home(false);
+        assert!(!buf.backspace());
+        assert_eq!(buf.cursor(), Pos::new(1, 0));
+
+        let mut buf = buffer("A\nB\nC");
+        buf.max_lines = 3;
+        assert!(!buf.insert('\n'));
+        assert!(buf.backspace() && buf.backspace());
+        assert!(buf.insert('\n'));
+        assert_eq!(buf.text(), "A\nB\n");
+
+        // columns are bytes, so a comment loaded from a file must not split a character
+        let mut buf = buffer("# caf\u{e9}\nNOP");
+        assert_eq!(buf.lines()[0], "# caf?");
+        buf.start_of_buffer(false);
+        for _ in 0..7 {
+            buf.move_right(false);
+        }
+        assert_eq!(buf.cursor(), Pos::new(1, 0));
+        assert!(buf.backspace());
+        assert_eq!(buf.text(), "# caf?NOP");
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut buf = buffer("MOV 1 A\nNOP\nJMP L");
+        buf.start_of_buffer(false);
+        buf.move_right(false);
+        buf.move_down(true);
+        assert_eq!(buf.selected_text().unwrap(), "OV 1 A\nN");
+        buf.cut();
+        assert_eq!(buf.text(), "MOP\nJMP L");
+        buf.end_of_buffer(false);
+        buf.paste();
+        assert_eq!(buf.text(), "MOP\nJMP LOV 1 A\nN");
+
+        buf.move_to(Pos::new(5, 9), false);
+        assert_eq!(buf.cursor(), Pos::new(2, 1));
+        buf.select(Pos::new(1, 0), Pos::new(1, 3));
+        assert_eq!(buf.selected_text().unwrap(), "JMP");
+
+        buf.select_all();
+        buf.copy();
+        buf.insert('X');
+        assert_eq!(buf.text(), "X");
+        // the clipboard holds more lines than fit in what is left of a 2 line buffer
+        buf.max_lines = 2;
+        assert!(!buf.paste());
+        assert_eq!(buf.blocked(), Some(Limit::Lines));
+
+        // a buffer over the limits can still be shortened
+        let mut buf = buffer("MOV 1 A\nNOP\nNOP");
+        buf.max_lines = 2;
+        buf.max_len = 3;
+        assert!(buf.backspace() && buf.backspace());
+        assert!(!buf.insert('\n'));
+        buf.start_of_buffer(false);
+        assert!(buf.delete());
+        assert!(!buf.insert('X'));
+        assert_eq!(buf.text(), "OV 1 A\nNOP\nN");
+    }
+
+    #[test]
+    fn test_scroll() {
+        // the spiral level allows more than the 24 by 16 characters the editor shows
+        let level = &crate::level::levels()[6];
+        let (max_lines, max_len) = (level.max_lines.unwrap(), level.max_len.unwrap());
+        assert!(max_lines > 24 && max_len > 16);
+        let mut buf = TextBuffer::new(max_lines, max_len);
+        let view = Pos::new(24, 16);
+        let mut scroll = Pos::new(0, 0);
+        for _ in 0..max_lines - 1 {
+            buf.insert('\n');
+            scroll = scroll.scroll_to(buf.cursor(), view);
+        }
+        for _ in 0..max_len {
+            buf.insert('A');
+            scroll = scroll.scroll_to(buf.cursor(), view);
+        }
+        assert_eq!(buf.cursor(), Pos::new(max_lines - 1, max_len));
+        assert_eq!(scroll, Pos::new(max_lines - 24, max_len - 15));
+        buf.start_of_buffer(false);
+        assert_eq!(scroll.scroll_to(buf.cursor(), view), Pos::new(0, 0));
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut buf = TextBuffer::new(24, 16);
+        for c in "NOP\nNEG A".chars() {
+            buf.insert(c);
+        }
+        buf.backspace();
+        assert!(buf.undo());
+        assert_eq!(buf.text(), "NOP\nNEG A");
+        assert!(buf.undo());
+        assert_eq!(buf.text(), "NOP\nNEG ");
+        assert!(buf.redo());
+        assert_eq!(buf.text(), "NOP\nNEG A");
+        buf.insert('X');
+        assert!(!buf.redo());
+        while buf.undo() {}
+        assert_eq!(buf.text(), "");
+    }
+}
@@ -0,0 +1,225 @@
+//! basic blocks and the control flow graph of an assembled program
+
+use crate::crab::OpCode;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// index of the label jumps to `lbl` land on
+pub fn find_label(code: &[OpCode], lbl: &str) -> Option<usize> {
+    code.iter().position(|op| match op {
+        OpCode::LABEL(l) => l == lbl,
+        _ => false,
+    })
+}
+
+/// where execution can go after `ip`, `code.len()` means it ran off the end
+///
+/// `JRO` with a register can land anywhere so every instruction is a successor
+pub fn successors(code: &[OpCode], ip: usize) -> Vec<usize> {
+    use crate::crab::OpCode::*;
+    match &code[ip] {
+        JMP(lbl) => find_label(code, lbl).into_iter().collect(),
+        JEZ(lbl) | JNZ(lbl) | JGZ(lbl) | JLZ(lbl) => {
+            find_label(code, lbl).into_iter().chain(Some(ip + 1)).collect()
+        }
+        JROI(n) => jroi_target(code, ip, *n).into_iter().collect(),
+        JRO(_) => (0..code.len()).collect(),
+        _ => vec![ip + 1],
+    }
+}
+
+fn jroi_target(code: &[OpCode], ip: usize, n: i32) -> Option<usize> {
+    let to = ip as i64 + n as i64;
+    if to >= 0 && to < code.len() as i64 { Some(to as usize) } else { None }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+    /// execution continues with the next instruction
+    Fallthrough,
+    /// unconditional jump
+    Jump,
+    /// conditional jump that was taken
+    Branch,
+    /// `JRO` with a register, may go anywhere
+    Computed,
+}
+
+impl EdgeKind {
+    fn name(self) -> &'static str {
+        match self {
+            EdgeKind::Fallthrough => "fallthrough",
+            EdgeKind::Jump => "jump",
+            EdgeKind::Branch => "branch",
+            EdgeKind::Computed => "computed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    /// target block, None when execution runs off the end of the program
+    pub to: Option<usize>,
+    pub kind: EdgeKind,
+}
+
+/// straight line run of instructions `start..end`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CfgFormat {
+    Dot,
+    Json,
+}
+
+impl FromStr for CfgFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dot" => Ok(CfgFormat::Dot),
+            "json" => Ok(CfgFormat::Json),
+            _ => Err(format!("unknown graph format {}", s)),
+        }
+    }
+}
+
+impl Cfg {
+    pub fn build(code: &[OpCode]) -> Cfg {
+        use crate::crab::OpCode::*;
+        // a block starts at the program start, at jump targets and after jumps
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (ip, op) in code.iter().enumerate() {
+            match op {
+                LABEL(_) => { leaders.insert(ip); }
+                JMP(_) | JEZ(_) | JNZ(_) | JGZ(_) | JLZ(_) | JRO(_) => { leaders.insert(ip + 1); }
+                JROI(n) => {
+                    leaders.insert(ip + 1);
+                    leaders.extend(jroi_target(code, ip, *n));
+                }
+                _ => (),
+            }
+        }
+        let leaders: Vec<_> = leaders.into_iter().filter(|l| *l < code.len()).collect();
+        let blocks: Vec<_> = leaders.iter().enumerate()
+            .map(|(i, start)| Block { start: *start, end: leaders.get(i + 1).cloned().unwrap_or(code.len()) })
+            .collect();
+        let block_of = |ip: usize| blocks.iter().position(|b| b.start == ip);
+
+        let mut edges = vec![];
+        for (from, block) in blocks.iter().enumerate() {
+            let last = block.end - 1;
+            let kind = match &code[last] {
+                JMP(_) | JROI(_) => EdgeKind::Jump,
+                JRO(_) => EdgeKind::Computed,
+                _ => EdgeKind::Fallthrough,
+            };
+            if let JRO(_) = &code[last] {
+                // every block, plus running off the end
+                for to in 0..blocks.len() {
+                    edges.push(Edge { from, to: Some(to), kind });
+                }
+                edges.push(Edge { from, to: None, kind });
+                continue;
+            }
+            for ip in successors(code, last) {
+                let kind = match &code[last] {
+                    JEZ(_) | JNZ(_) | JGZ(_) | JLZ(_) if ip != last + 1 => EdgeKind::Branch,
+                    _ => kind,
+                };
+                edges.push(Edge { from, to: block_of(ip), kind });
+            }
+        }
+        Cfg { blocks, edges }
+    }
+
+    /// Graphviz source, `source` holds the text of every instruction
+    pub fn to_dot(&self, source: &[String]) -> String {
+        let mut out = String::from("digraph crab {\n    node [shape=box, fontname=monospace];\n");
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut label = format!("L{}-{}\\l", block.start + 1, block.end);
+            for line in &source[block.start..block.end] {
+                label.push_str(&line.replace('\\', "\\\\").replace('"', "\\\""));
+                label.push_str("\\l");
+            }
+            writeln!(out, "    b{} [label=\"{}\"];", i, label).unwrap();
+        }
+        if self.edges.iter().any(|e| e.to.is_none()) {
+            out.push_str("    end [label=\"END\", shape=ellipse];\n");
+        }
+        for edge in &self.edges {
+            let to = edge.to.map(|b| format!("b{}", b)).unwrap_or("end".to_owned());
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Branch => " [label=\"branch\", color=blue]",
+                EdgeKind::Computed => " [style=dashed]",
+            };
+            writeln!(out, "    b{} -> {}{};", edge.from, to, style).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_json(&self, source: &[String]) -> String {
+        let blocks: Vec<_> = self.blocks.iter().enumerate().map(|(i, b)| {
+            let instrs: Vec<_> = source[b.start..b.end].iter()
+                .map(|l| format!("\"{}\"", l.replace('\\', "\\\\").replace('"', "\\\"")))
+                .collect();
+            format!("{{\"id\":{},\"start\":{},\"end\":{},\"instrs\":[{}]}}", i, b.start, b.end, instrs.join(","))
+        }).collect();
+        let edges: Vec<_> = self.edges.iter().map(|e| {
+            let to = e.to.map(|b| b.to_string()).unwrap_or("null".to_owned());
+            format!("{{\"from\":{},\"to\":{},\"kind\":\"{}\"}}", e.from, to, e.kind.name())
+        }).collect();
+        format!("{{\"blocks\":[{}],\"edges\":[{}]}}\n", blocks.join(","), edges.join(","))
+    }
+
+    pub fn export(&self, source: &[String], format: CfgFormat) -> String {
+        match format {
+            CfgFormat::Dot => self.to_dot(source),
+            CfgFormat::Json => self.to_json(source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crab::Crab;
+
+    #[test]
+    fn test_build() {
+        let mut crab = Crab::new();
+        crab.load_code("MOV 3 A\nL:\nSUB 1 A\nJGZ L\nJRO 1\nNOP").unwrap();
+        let cfg = Cfg::build(&crab.code);
+        assert_eq!(cfg.blocks, vec![
+            Block { start: 0, end: 1 },
+            Block { start: 1, end: 4 },
+            Block { start: 4, end: 5 },
+            Block { start: 5, end: 6 },
+        ]);
+        assert_eq!(cfg.edges, vec![
+            Edge { from: 0, to: Some(1), kind: EdgeKind::Fallthrough },
+            Edge { from: 1, to: Some(1), kind: EdgeKind::Branch },
+            Edge { from: 1, to: Some(2), kind: EdgeKind::Fallthrough },
+            Edge { from: 2, to: Some(3), kind: EdgeKind::Jump },
+            Edge { from: 3, to: None, kind: EdgeKind::Fallthrough },
+        ]);
+        let source: Vec<_> = "MOV 3 A\nL:\nSUB 1 A\nJGZ L\nJRO 1\nNOP".lines().map(String::from).collect();
+        assert!(cfg.to_dot(&source).contains("b1 -> b1 [label=\"branch\", color=blue];"));
+        assert!(cfg.to_json(&source).contains("{\"from\":3,\"to\":null,\"kind\":\"fallthrough\"}"));
+    }
+}
@@ -1,4 +1,21 @@
 use crate::prelude::*;
+use crate::history::{History, Snapshot, HISTORY_LEN};
+use crate::debugger::{Debugger, Watch};
+use crate::level::{self, Level};
+use crate::sim::{self, Sim};
+use crate::crab::{RuntimeError, REGISTERS};
+use crate::trace::{Trace, TraceFormat};
+use crate::replay::Replay;
+use crate::lint::{lint, Lint};
+use crate::synth::{Goal, Synthesis};
+use crate::solver::{self, Profile};
+use crate::generator::{Generation, Params};
+use crate::editor::{Edit, Limit, Pos, TextBuffer};
+use crate::syntax::{self, SyntaxError, TokenKind};
+use crate::complete::{self, Completion};
+use crate::help::{self, Topic};
+use crate::widget::{Bounds, Kind, State, Ui, Widget};
+use std::fs::File;
 
 const ORIGIN_X: f32 = 220.;
 const ORIGIN_Y: f32 = 95.;
@@ -6,132 +23,691 @@ const TILE_Y: f32 = 28.;
 const TILE_X: f32 = 34.;
 const OFFSET: f32 = -12.;
 
+/// shown at the end of the prompt text
 const CURSOR: char = '_';
 const TEXT_EDITOR_X: f32 = 15.;
 const TEXT_EDITOR_Y: f32 = 34.;
 const LINE_HEIGHT: f32 = 9.;
 const CHAR_WIDTH: f32 = 5.;
-const MAX_LEN: usize = 2 << 3;
-const MAX_LINES: usize = 24;
+/// lines and columns the editor shows at once, longer programs scroll
+const VIEW_LINES: usize = 24;
+const VIEW_COLS: usize = 16;
+/// suggestions the completion popup lists
+const SUGGESTIONS: usize = 5;
+/// longest program `show_solution` searches for
+const HINT_LEN: usize = 4;
+/// cycles the search of `show_solution` may run each frame, so the game keeps drawing meanwhile
+const HINT_CYCLES: usize = 50_000;
+/// states the straight line fallback of `show_solution` may explore
+const HINT_STATES: usize = 200_000;
+/// states the straight line fallback may take off its queue each frame
+const HINT_FRAME_STATES: usize = 10_000;
+/// solver states the generation of the next level may explore each frame
+const GENERATION_STATES: usize = 10_000;
+/// breakpoints are toggled by clicking left of this
+const GUTTER_W: f32 = 10.;
+/// cycles it takes a step of the trail to fade away
+const TRAIL_FADE: f32 = 40.;
+
+/// most milliseconds between the clicks of a double click
+const DOUBLE_CLICK: f64 = 400.;
+
+/// warnings are marked at the right edge of the editor
+const LINT_X: f32 = 96.;
+
+const STATUS_X: f32 = 8.;
+const STATUS_Y: f32 = 262.;
 
 const REG_Y: f32 = 250.;
 const REG_X: f32 = 14.;
 const REG_OFFSET: f32 = 20.;
+/// input and output panels, to the right of the registers
+const STREAM_X: f32 = 110.;
+const STREAM_Y: f32 = 230.;
+
+/// replays are saved to and loaded from this file in the working directory
+const REPLAY_FILE: &str = "replay.txt";
 
 /// continuous stepping delay time in ms
 const PLAY_DELAY: f64 = 100.;
 
-const GRID_W: usize = 8;
-const GRID_H: usize = 6;
+/// top of the text buttons along the top of the screen
+const CONTROLS_Y: f32 = 8.5;
+/// top of the level selection in the bottom right corner
+const LEVEL_Y: f32 = 245.5;
+
+/// the reference covers the level, leaving the editor free to hover
+const REFERENCE_X: f32 = 104.;
+const REFERENCE_Y: f32 = 24.;
+const REFERENCE_W: f32 = 370.;
+const REFERENCE_H: f32 = 232.;
+/// width of the column listing the topics
+const TOPICS_W: f32 = 40.;
+/// characters in a line of a topic's text
+const REFERENCE_COLS: usize = 62;
+
+const TIMELINE_X: f32 = 120.;
+const TIMELINE_Y: f32 = 262.;
+const TIMELINE_W: f32 = 280.;
+const TIMELINE_H: f32 = 4.;
+
+
+/// the searches `show_solution` runs a little every frame, one after the other
+enum Hint {
+    /// a short loop, tried first
+    Loop(Box<Synthesis>),
+    /// a straight line program, which only fits the one layout it was made for
+    Straight(solver::Search),
+}
+
+/// play mode speed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    Slow,
+    Normal,
+    Fast,
+    Turbo,
+}
+
+impl Speed {
+    /// delay between updates in ms
+    fn delay(self) -> f64 {
+        match self {
+            Speed::Slow => 3. * PLAY_DELAY,
+            Speed::Normal => PLAY_DELAY,
+            Speed::Fast => PLAY_DELAY / 4.,
+            Speed::Turbo => 0.,
+        }
+    }
+
+    /// cycles executed per update
+    fn steps(self) -> usize {
+        match self {
+            Speed::Turbo => 50,
+            _ => 1,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Speed::Slow => Speed::Normal,
+            Speed::Normal => Speed::Fast,
+            Speed::Fast => Speed::Turbo,
+            Speed::Turbo => Speed::Slow,
+        }
+    }
+}
+
+/// what the widgets are for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Control {
+    Play,
+    Step,
+    Stop,
+    Speed,
+    FastForward,
+    Help,
+    PrevLevel,
+    Level,
+    NextLevel,
+    /// behind the reference and the key bindings
+    Overlay,
+}
 
-type Grid = Vec<Vec<bool>>;
+/// the buttons, with play, step and stop on the icons of the background
+fn controls() -> Ui<Control> {
+    Ui::new(vec![
+        Widget::button(Control::Play, Bounds::new(21.5, 8., 8.7, 10.3), None, "Run the program, click again to pause"),
+        Widget::button(Control::Step, Bounds::new(46.2, 7.7, 12.1, 11.8), None, "Run one instruction"),
+        Widget::button(Control::Stop, Bounds::new(74., 7., 9.9, 11.9), None, "Stop the program"),
+        Widget::button(Control::Speed, Bounds::new(180., CONTROLS_Y, 70., LINE_HEIGHT), Some(""), "Change the play speed"),
+        Widget::button(Control::FastForward, Bounds::new(260., CONTROLS_Y, 30., LINE_HEIGHT), Some("END >>"), "Run to the end at once"),
+        Widget::button(Control::Help, Bounds::new(300., CONTROLS_Y, 25., LINE_HEIGHT), Some("HELP"), "Instructions and registers"),
+        Widget::button(Control::PrevLevel, Bounds::new(404., LEVEL_Y, 8., LINE_HEIGHT), Some("<"), "Previous level"),
+        Widget::label(Control::Level, Bounds::new(414., LEVEL_Y, 50., LINE_HEIGHT), ""),
+        Widget::button(Control::NextLevel, Bounds::new(466., LEVEL_Y, 8., LINE_HEIGHT), Some(">"), "Next level, once reached"),
+        Widget::panel(Control::Overlay, Bounds::new(REFERENCE_X, REFERENCE_Y, REFERENCE_W, REFERENCE_H)),
+    ])
+}
+
+/// single line input shown under the editor
+enum Prompt {
+    /// condition for the breakpoint on the given line
+    Condition(usize, String),
+    /// register name or POS, toggles a watchpoint
+    Watch(String),
+}
 
 pub struct Game {
-    crab: Crab,
-    buf: String,
+    sim: Sim,
+    editor: TextBuffer,
+    /// first line and column the editor shows
+    scroll: Pos,
     is_debugging: bool,
     is_playing: bool,
     code_finished: bool,
     sleep: f64,
     error: Option<usize>,
-    current_grid: Grid,
     current_level: usize,
-    levels: Vec<Grid>,
+    levels: Vec<Level>,
+    /// furthest cycle reached in this run, kept when stepping back
+    furthest: usize,
+    history: History,
+    debugger: Debugger,
+    prompt: Option<Prompt>,
+    /// why the program was paused, or the last debugger error
+    status: Option<String>,
+    speed: Speed,
+    /// warnings for the code in the editor
+    lints: Vec<Lint>,
+    /// lines of the code in the editor that do not assemble
+    syntax_errors: Vec<(usize, SyntaxError)>,
+    topics: Vec<Topic>,
+    /// the topic shown while the reference is open
+    reference: Option<usize>,
+    /// the key bindings, shown over the level while set
+    key_help: Option<Vec<String>>,
+    ui: Ui<Control>,
+    /// milliseconds since the game started
+    clock: f64,
+    /// when and where the editor was last clicked
+    last_click: Option<(f64, Pos)>,
+    /// set while the mouse is held down after a click in the editor
+    dragging: bool,
+    /// tiles the crab walked over this run, with the cycle it got to each
+    trail: Vec<(usize, (i32, i32))>,
+    /// tiles the program in the editor would walk over, shown while it is not running
+    ghost: Vec<(i32, i32)>,
+    /// furthest level reached, the ones up to it can be picked
+    unlocked: usize,
+    /// the search `show_solution` started, with the level it is for
+    hint: Option<(usize, Hint)>,
+    /// the level being generated to follow the last one
+    generation: Option<Generation>,
+    /// why the generator could not make a level past the last one, so it is not retried
+    no_next_level: Option<String>,
+    /// set when the program crashed, as opposed to halting normally
+    runtime_error: Option<RuntimeError>,
+    /// index of the variant of the level being run
+    variant: usize,
+    /// per variant, Some(true) once cleared and Some(false) once failed
+    results: Vec<Option<bool>>,
 }
 
 impl Game {
-    fn init_levels() -> Vec<Grid> {
-        let mut ret = vec![];
-
-        ret.push(to_grid(include_str!("levels/tutorial.txt")));
-        ret.push(to_grid(include_str!("levels/tutorial2.txt")));
-        ret.push(to_grid(include_str!("levels/tutorial3.txt")));
-        ret.push(to_grid(include_str!("levels/tutorial4.txt")));
-        ret.push(to_grid(include_str!("levels/2.txt")));
-        ret.push(to_grid(include_str!("levels/1.txt")));
-        ret.push(to_grid(include_str!("levels/test.txt")));
-        ret.push(to_grid(include_str!("levels/impossible.txt")));
-
-        ret
-    }
-
     pub fn new() -> Self {
-        let crab = Crab::new();
-        let buf = CURSOR.to_string();
-        let levels = Game::init_levels();
+        let levels = level::levels();
+        let results = vec![None; levels[0].grids.len()];
         Self {
-            crab,
-            buf,
+            sim: Sim::new(levels[0].grids[0].clone()),
+            editor: TextBuffer::new(levels[0].max_lines.unwrap_or(usize::MAX), levels[0].max_len.unwrap_or(usize::MAX)),
+            scroll: Pos::new(0, 0),
             is_debugging: false,
             is_playing: false,
             code_finished: false,
             sleep: 0.,
             error: None,
             current_level: 0,
-            current_grid: levels[0].clone(),
             levels,
+            furthest: 0,
+            history: History::new(HISTORY_LEN),
+            debugger: Debugger::new(),
+            prompt: None,
+            status: None,
+            speed: Speed::Normal,
+            lints: vec![],
+            syntax_errors: vec![],
+            topics: help::topics(),
+            reference: None,
+            key_help: None,
+            ui: controls(),
+            clock: 0.,
+            last_click: None,
+            dragging: false,
+            trail: vec![],
+            ghost: vec![],
+            unlocked: 0,
+            hint: None,
+            generation: None,
+            no_next_level: None,
+            runtime_error: None,
+            variant: 0,
+            results,
         }
     }
 
     pub fn update(&mut self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()>  {
         let rate = window.update_rate();
+        self.clock += rate;
+        self.ui.update(rate);
+        self.sync_controls();
+        self.poll_hint();
+        self.poll_generation(sprites);
         self.sleep -= rate;
         if self.sleep < 0. && self.is_playing && !self.code_finished {
-            self.step(sprites);
-            self.sleep = PLAY_DELAY;
-            if self.code_finished {
-                self.is_playing = false;
+            self.sleep = self.speed.delay();
+            for _ in 0..self.speed.steps() {
+                let before = self.sim.crab.save();
+                self.step(sprites);
+                if self.code_finished {
+                    self.is_playing = false;
+                } else if let Some(reason) = self.debugger.check(&before, &self.sim.crab, self.sim.cycle) {
+                    // pause, leaving the debugger where it is
+                    self.is_playing = false;
+                    self.status = Some(reason);
+                }
+                if !self.is_playing { break }
             }
         }
         Ok(())
     }
 
     pub fn char(&mut self, c: char) {
+        if self.prompt.is_some() {
+            self.prompt_char(c);
+            return;
+        }
         if self.is_debugging { return }
-        // backspace
-        if c == '\0' {
-            let idx = self.buf.find(CURSOR).unwrap();
-            if idx != 0 {
-                self.buf.remove(idx-1);
+        if self.editor.insert(c) {
+            self.relint();
+        }
+        self.scroll_to(self.editor.cursor());
+    }
+
+    /// runs an editor command, the text can only be changed while no program runs
+    pub fn edit(&mut self, edit: Edit, select: bool) {
+        if self.prompt.is_some() {
+            if edit == Edit::Backspace {
+                self.prompt_char('\0');
             }
             return;
         }
-        // cannot be greater than max line length
-        if let Some(len) = self.buf.lines().last().map(|i|i.len()) {
-            if len + 1 == MAX_LEN {
-                return;
+        if self.is_debugging && edit.modifies() { return }
+        if self.editor.apply(edit, select) && edit.modifies() {
+            self.relint();
+        }
+        self.scroll_to(self.editor.cursor());
+    }
+
+    /// suggestions for the word before the cursor
+    fn completion(&self) -> Option<Completion> {
+        if self.is_debugging || self.prompt.is_some() || self.editor.selection().is_some() {
+            return None;
+        }
+        let cursor = self.editor.cursor();
+        let lines = self.editor.lines();
+        complete::complete(&lines[cursor.line], cursor.col, &complete::labels(lines))
+    }
+
+    /// finishes the word before the cursor with the first suggestion
+    pub fn accept_completion(&mut self) {
+        let rest = match self.completion() {
+            Some(completion) => completion.rest(self.editor.cursor().col),
+            None => return,
+        };
+        if !rest.is_empty() && self.editor.insert_str(&rest) {
+            self.relint();
+        }
+        self.scroll_to(self.editor.cursor());
+    }
+
+    /// scrolls the editor just enough to show `pos`
+    fn scroll_to(&mut self, pos: Pos) {
+        self.scroll = self.scroll.scroll_to(pos, Pos::new(VIEW_LINES, VIEW_COLS));
+    }
+
+    /// scrolls the editor to a line, leaving the columns alone
+    fn show_line(&mut self, line: usize) {
+        let line = line.min(self.editor.lines().len() - 1);
+        self.scroll_to(Pos::new(line, self.scroll.col));
+    }
+
+    /// scrolls the editor by `lines`, up if negative
+    pub fn scroll(&mut self, lines: i32) {
+        let last = self.editor.lines().len().saturating_sub(VIEW_LINES) as i32;
+        self.scroll.line = (self.scroll.line as i32 + lines).min(last).max(0) as usize;
+    }
+
+    fn limit_status(&self, limit: Limit) -> Option<String> {
+        let text = match limit {
+            Limit::Lines => format!("this level allows {} lines", self.editor.max_lines),
+            Limit::Width => format!("this level allows {} characters a line", self.editor.max_len),
+        };
+        Some(text.to_uppercase())
+    }
+
+    /// center of line `line` on screen, None while it is scrolled out of view
+    fn line_y(&self, line: usize) -> Option<f32> {
+        let row = line.checked_sub(self.scroll.line).filter(|row| *row < VIEW_LINES)?;
+        Some(TEXT_EDITOR_Y + LINE_HEIGHT * row as f32)
+    }
+
+    /// center of column `col` on screen, which can be outside the editor
+    fn col_x(&self, col: usize) -> f32 {
+        TEXT_EDITOR_X + CHAR_WIDTH * (col as f32 - self.scroll.col as f32)
+    }
+
+    /// the line and column of the character cell under a point, None outside the editor
+    ///
+    /// the position can be past the end of its line or below the last line
+    fn pos_at(&self, x: f32, y: f32) -> Option<Pos> {
+        let row = ((y - TEXT_EDITOR_Y) / LINE_HEIGHT + 0.5).floor();
+        let col = ((x - TEXT_EDITOR_X) / CHAR_WIDTH + 0.5).floor();
+        if row < 0. || row >= VIEW_LINES as f32 || col < 0. || x > LINT_X {
+            return None;
+        }
+        Some(Pos::new(self.scroll.line + row as usize, self.scroll.col + col as usize))
+    }
+
+    /// where a click puts the cursor, the gap between characters nearest to the point
+    fn gap_at(&self, x: f32, y: f32) -> Option<Pos> {
+        self.pos_at(x + CHAR_WIDTH * 0.5, y)
+    }
+
+    /// places the cursor where the editor was clicked, extending the selection if `select`,
+    /// a double click selects the word under the mouse, returns false outside the editor
+    pub fn click_editor(&mut self, x: f32, y: f32, select: bool) -> bool {
+        if self.prompt.is_some() { return false }
+        let pos = match self.gap_at(x, y) {
+            Some(pos) => pos,
+            None => return false,
+        };
+        let double = matches!(self.last_click, Some((time, at)) if at == pos && self.clock - time < DOUBLE_CLICK);
+        self.editor.move_to(pos, select);
+        let token = self.pos_at(x, y).and_then(|cell| {
+            let line = self.editor.lines().get(cell.line)?;
+            let token = syntax::tokenize(line).into_iter().find(|t| (t.start..t.end()).contains(&cell.col))?;
+            Some((Pos::new(cell.line, token.start), Pos::new(cell.line, token.end())))
+        });
+        if let (true, Some((start, end))) = (double, token) {
+            self.editor.select(start, end);
+        }
+        // a third click starts over
+        self.last_click = if double { None } else { Some((self.clock, pos)) };
+        self.dragging = !double;
+        self.scroll_to(self.editor.cursor());
+        true
+    }
+
+    /// extends the selection to the mouse while it is held after a click in the editor,
+    /// scrolling when it leaves the top or bottom, returns false if no text is being dragged
+    pub fn drag(&mut self, x: f32, y: f32) -> bool {
+        if !self.dragging { return false }
+        let top = TEXT_EDITOR_Y - LINE_HEIGHT * 0.5;
+        let bottom = top + LINE_HEIGHT * VIEW_LINES as f32;
+        if y < top {
+            self.scroll(-1);
+        } else if y >= bottom {
+            self.scroll(1);
+        }
+        let x = x.clamp(TEXT_EDITOR_X - CHAR_WIDTH * 0.5, LINT_X - CHAR_WIDTH * 0.5);
+        if let Some(pos) = self.gap_at(x, y.clamp(top, bottom - 1.)) {
+            self.editor.move_to(pos, true);
+            self.scroll_to(self.editor.cursor());
+        }
+        true
+    }
+
+    /// checks the buffer again, leaving the old warnings while it does not assemble
+    fn relint(&mut self) {
+        self.syntax_errors = self.editor.lines().iter().enumerate()
+            .filter_map(|(i, line)| syntax::parse_line(line).err().map(|e| (i, e)))
+            .collect();
+        let mut crab = Crab::new();
+        if crab.load_code(&self.editor.text()).is_ok() {
+            self.lints = lint(&crab.code);
+        }
+        self.predict();
+    }
+
+    /// runs the program on the first variant up to the cycle limit for the ghost path,
+    /// which is left empty for programs that cannot run
+    fn predict(&mut self) {
+        self.ghost.clear();
+        let level = &self.levels[self.current_level];
+        let lines = self.editor.lines();
+        if lines.len() > self.editor.max_lines || lines.iter().any(|l| l.len() > self.editor.max_len) {
+            return;
+        }
+        let mut sim = Sim::new(level.grids[0].clone());
+        sim.set_streams(level.input.clone(), level.output.clone());
+        if sim.load_code(&self.editor.text()).is_ok() {
+            self.ghost = sim.run_path(level.cycle_limit).0;
+        }
+    }
+
+    /// adds the crab's tile to the trail if it moved
+    fn mark_trail(&mut self) {
+        let pos = (self.sim.crab.pos_x, self.sim.crab.pos_y);
+        if self.trail.last().map(|(_, last)| *last) != Some(pos) {
+            self.trail.push((self.sim.cycle, pos));
+        }
+    }
+
+    fn prompt_char(&mut self, c: char) {
+        let text = match self.prompt.as_mut() {
+            Some(Prompt::Condition(_, text)) | Some(Prompt::Watch(text)) => text,
+            None => return,
+        };
+        match c {
+            '\0' => { text.pop(); }
+            '\n' => self.commit_prompt(),
+            c => text.push(c),
+        }
+    }
+
+    fn commit_prompt(&mut self) {
+        self.status = None;
+        match self.prompt.take() {
+            Some(Prompt::Condition(line, text)) => {
+                if text.trim().is_empty() {
+                    self.debugger.set_condition(line, None);
+                    return;
+                }
+                match text.parse() {
+                    Ok(cond) => self.debugger.set_condition(line, Some(cond)),
+                    Err(e) => self.status = Some(e.to_uppercase()),
+                }
             }
+            Some(Prompt::Watch(text)) => {
+                match text.parse::<Watch>() {
+                    Ok(watch) => self.debugger.toggle_watch(watch),
+                    Err(e) => self.status = Some(e.to_uppercase()),
+                }
+            }
+            None => (),
         }
-        if c == '\n' {
-            // disallow double newline
-            let idx = self.buf.find(CURSOR).unwrap();
-            if idx == 0 {
-                return;
+    }
+
+    pub fn toggle_reference(&mut self) {
+        self.key_help = None;
+        self.reference = match self.reference {
+            Some(_) => None,
+            // open on the instruction under the cursor
+            None => {
+                let line = &self.editor.lines()[self.editor.cursor().line];
+                let first = syntax::tokenize(line).into_iter().next();
+                Some(first.and_then(|token| help::find(&self.topics, token.text)).unwrap_or(0))
             }
-            let prev = &self.buf[(idx-1)..idx];
-            if prev == "\n" || prev == " " {
-                return;
+        };
+    }
+
+    /// shows `lines` over the level, or hides them if they are shown
+    pub fn toggle_key_help(&mut self, lines: Vec<String>) {
+        self.reference = None;
+        self.key_help = match self.key_help {
+            Some(_) => None,
+            None => Some(lines),
+        };
+    }
+
+    /// the reference or the key bindings are shown
+    pub fn overlay_open(&self) -> bool {
+        self.reference.is_some() || self.key_help.is_some()
+    }
+
+    pub fn close_overlay(&mut self) {
+        self.reference = None;
+        self.key_help = None;
+    }
+
+    /// brings the widgets up to date with the game
+    fn sync_controls(&mut self) {
+        self.ui.set_text(Control::Speed, &format!("SPEED: {:?}", self.speed).to_uppercase());
+        self.ui.set_text(Control::Level, &format!("Level: {}/{}", self.current_level + 1, self.levels.len()));
+        self.ui.set_enabled(Control::Stop, self.is_debugging);
+        self.ui.set_enabled(Control::PrevLevel, self.current_level > 0);
+        self.ui.set_enabled(Control::NextLevel, self.current_level < self.unlocked);
+        self.ui.get_mut(Control::Overlay).visible = self.overlay_open();
+    }
+
+    /// true if the mouse went down on a button, which is clicked once it is released
+    pub fn mouse_down(&mut self, x: f32, y: f32) -> bool {
+        self.ui.mouse_down(x, y)
+    }
+
+    /// true if a button was clicked
+    pub fn mouse_up(&mut self, x: f32, y: f32, sprites: &mut Asset<Sprites>) -> bool {
+        self.dragging = false;
+        match self.ui.mouse_up(x, y) {
+            Some(control) => {
+                self.click(control, sprites);
+                true
             }
-            // check number of lines
-            if self.buf.lines().collect::<Vec<_>>().len() + 1 > MAX_LINES { return; }
+            None => false,
         }
-        self.buf = self.buf.replace(CURSOR, &format!("{}{}", c, CURSOR));
     }
 
-    pub fn cursor_left(&mut self) {
-        let idx = self.buf.find(CURSOR).unwrap();
-        if idx != 0 {
-            let ch = self.buf.remove(idx-1);
-            self.buf.insert(idx, ch);
+    fn click(&mut self, control: Control, sprites: &mut Asset<Sprites>) {
+        match control {
+            Control::Play => self.play(),
+            Control::Step => self.step(sprites),
+            Control::Stop => self.stop(),
+            Control::Speed => self.cycle_speed(),
+            Control::FastForward => self.fast_forward(sprites),
+            Control::Help => self.toggle_reference(),
+            Control::PrevLevel => {
+                if let Some(level) = self.current_level.checked_sub(1) {
+                    self.select_level(level);
+                }
+            }
+            Control::NextLevel => self.select_level(self.current_level + 1),
+            Control::Level | Control::Overlay => {}
+        }
+    }
+
+    /// moves the keyboard focus along the buttons, backwards if `back`
+    pub fn focus_next(&mut self, back: bool) {
+        self.sync_controls();
+        self.ui.focus_next(back);
+    }
+
+    /// clicks the button with the keyboard focus, false if none has it
+    pub fn activate(&mut self, sprites: &mut Asset<Sprites>) -> bool {
+        match self.ui.focused() {
+            Some(control) => {
+                self.click(control, sprites);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// true if a button had the focus
+    pub fn clear_focus(&mut self) -> bool {
+        self.ui.clear_focus()
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = Some(status.to_uppercase());
+    }
+
+    /// highlights the button under the mouse, and while the reference is open
+    /// shows the topic under the mouse, in its list or in the editor
+    pub fn hover(&mut self, x: f32, y: f32) {
+        self.ui.mouse_moved(x, y);
+        if self.reference.is_none() { return }
+        let topic = if (REFERENCE_X..REFERENCE_X + TOPICS_W).contains(&x) {
+            let row = ((y - REFERENCE_Y - 2.) / LINE_HEIGHT).floor();
+            if row < 0. { return }
+            Some(row as usize).filter(|row| *row < self.topics.len())
+        } else if let Some(pos) = self.pos_at(x, y).filter(|pos| pos.line < self.editor.lines().len()) {
+            syntax::tokenize(&self.editor.lines()[pos.line]).into_iter()
+                .find(|t| (t.start..t.end()).contains(&pos.col))
+                .filter(|t| t.kind == TokenKind::Mnemonic || t.kind == TokenKind::Register)
+                .and_then(|t| help::find(&self.topics, t.text))
+        } else {
+            None
+        };
+        if topic.is_some() {
+            self.reference = topic;
+        }
+    }
+
+    pub fn is_debugging(&self) -> bool {
+        self.is_debugging
+    }
+
+    pub fn prompt_open(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    pub fn close_prompt(&mut self) {
+        self.prompt = None;
+    }
+
+    /// asks for a condition for the breakpoint on the cursor line
+    pub fn edit_condition(&mut self) {
+        self.prompt = Some(Prompt::Condition(self.cursor_line(), String::new()));
+    }
+
+    /// asks for a register or POS to watch
+    pub fn edit_watch(&mut self) {
+        self.prompt = Some(Prompt::Watch(String::new()));
+    }
+
+    pub fn toggle_breakpoint(&mut self) {
+        let line = self.cursor_line();
+        self.debugger.toggle_breakpoint(line);
+    }
+
+    /// toggles the breakpoint on the clicked line, returns false if the click missed the gutter
+    pub fn click_gutter(&mut self, x: f32, y: f32) -> bool {
+        if !(0. ..=GUTTER_W).contains(&x) {
+            return false;
+        }
+        let row = ((y - TEXT_EDITOR_Y) / LINE_HEIGHT).round();
+        if row < 0. || row >= VIEW_LINES as f32 {
+            return false;
+        }
+        let line = self.scroll.line + row as usize;
+        if line >= self.editor.lines().len() {
+            return false;
         }
+        self.debugger.toggle_breakpoint(line);
+        true
     }
 
-    pub fn cursor_right(&mut self) {
-        let idx = self.buf.find(CURSOR).unwrap();
-        if idx != self.buf.len() - 1 {
-            let ch = self.buf.remove(idx);
-            self.buf.insert(idx+1, ch);
+    /// first warning on the cursor line
+    fn cursor_lint(&self) -> Option<&Lint> {
+        let line = self.cursor_line();
+        self.lints.iter().find(|l| l.line == line)
+    }
+
+    /// the error on the cursor line, otherwise the first warning there, otherwise the first error
+    fn diagnostic(&self) -> Option<String> {
+        let line = self.cursor_line();
+        if let Some((_, e)) = self.syntax_errors.iter().find(|(l, _)| *l == line) {
+            return Some(e.to_string());
+        }
+        if let Some(lint) = self.cursor_lint() {
+            return Some(lint.kind.to_string());
         }
+        self.syntax_errors.first().map(|(l, e)| format!("line {}: {}", l + 1, e))
+    }
+
+    fn cursor_line(&self) -> usize {
+        self.editor.cursor().line
     }
 }
 
@@ -139,19 +715,311 @@ impl Game {
     pub fn draw(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
         self.draw_crab(window, sprites)?;
         self.draw_grid_items(window, sprites)?;
+        self.draw_paths(window);
         self.draw_text(window, sprites)?;
         self.draw_registers(window, sprites)?;
+        self.draw_streams(window, sprites)?;
         self.draw_debugger(window, sprites)?;
         self.draw_error(window, sprites)?;
-        self.draw_level(window, sprites)?;
+        self.draw_variants(window, sprites)?;
+        self.draw_budget(window, sprites)?;
+        self.draw_limits(window, sprites)?;
+        self.draw_timeline(window, sprites)?;
+        self.draw_breakpoints(window)?;
+        self.draw_lints(window)?;
+        self.draw_status(window, sprites)?;
+        self.draw_widgets(window, sprites)?;
+        self.draw_completion(window, sprites)?;
+        self.draw_reference(window, sprites)?;
+        self.draw_key_help(window, sprites)?;
+        Ok(())
+    }
+
+    /// buttons on the background get a tint for their state, the others a box as well
+    fn draw_widgets(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let panel = Color{r:235./255., g:235./255., b:220./255., a:255./255.};
+        let disabled = Color{r:150./255., g:150./255., b:150./255., a:255./255.};
+        for widget in self.ui.widgets().iter().filter(|w| w.visible) {
+            let b = widget.bounds;
+            let rect = Rectangle::new((b.x, b.y), (b.w, b.h));
+            let (text, z) = match &widget.kind {
+                Kind::Panel => {
+                    window.draw_ex(&rect, Col(panel), Transform::scale(Vector::new(1., 1.)), 6);
+                    continue;
+                }
+                Kind::Label(text) => (text, 1),
+                Kind::Button(text) => {
+                    let tint = match self.ui.state(widget.id) {
+                        State::Normal => None,
+                        State::Hover => Some(Color{r:1., g:1., b:1., a:0.4}),
+                        State::Pressed => Some(Color{r:0., g:0., b:0., a:0.3}),
+                        State::Disabled => Some(Color{r:0.5, g:0.5, b:0.5, a:0.4}),
+                    };
+                    if let Some(tint) = tint {
+                        window.draw_ex(&rect, Col(tint), Transform::scale(Vector::new(1., 1.)), 1);
+                    }
+                    if self.ui.focused() == Some(widget.id) {
+                        self.draw_outline(window, rect, 3);
+                    }
+                    match text {
+                        Some(text) => (text, 2),
+                        None => continue,
+                    }
+                }
+            };
+            let color = if widget.enabled { Color::BLACK } else { disabled };
+            sprites.execute(|spr|{
+                let img = spr.render_colored(text, color);
+                // left aligned, the image is scaled around its center
+                let w = img.area().width() * 0.1;
+                window.draw_ex(&
+                    img.area().with_center((b.x + w * 0.5, b.y + b.h * 0.5)),
+                    Img(&img),
+                    Transform::scale(Vector::new(0.1, 0.1)),
+                    z,
+                );
+                Ok(())
+            })?;
+        }
+        self.draw_tooltip(window, sprites)
+    }
+
+    fn draw_outline(&self, window: &mut Window, rect: Rectangle, z: i32) {
+        let (x, y, w, h) = (rect.pos.x, rect.pos.y, rect.size.x, rect.size.y);
+        let sides = [((x, y), (w, 1.)), ((x, y + h - 1.), (w, 1.)), ((x, y), (1., h)), ((x + w - 1., y), (1., h))];
+        for (pos, size) in sides.iter() {
+            window.draw_ex(&Rectangle::new(*pos, *size), Col(Color::BLACK), Transform::scale(Vector::new(1., 1.)), z);
+        }
+    }
+
+    /// below the widget, moved left if it would leave the screen
+    fn draw_tooltip(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let (text, b) = match self.ui.tooltip() {
+            Some(tooltip) => tooltip,
+            None => return Ok(()),
+        };
+        sprites.execute(|spr|{
+            let img = spr.render_str(text);
+            let w = img.area().width() * 0.1 + 4.;
+            let x = b.x.min(WIDTH - w);
+            // widgets at the bottom get theirs above
+            let y = if b.y + b.h + LINE_HEIGHT > HEIGHT { b.y - LINE_HEIGHT - 1. } else { b.y + b.h + 1. };
+            let rect = Rectangle::new((x, y), (w, LINE_HEIGHT));
+            window.draw_ex(&rect, Col(Color{r:1., g:1., b:210./255., a:1.}), Transform::scale(Vector::new(1., 1.)), 9);
+            self.draw_outline(window, rect, 9);
+            window.draw_ex(&
+                img.area().with_center((x + w * 0.5, y + LINE_HEIGHT * 0.5)),
+                Img(&img),
+                Transform::scale(Vector::new(0.1, 0.1)),
+                10,
+            );
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn draw_breakpoints(&self, window: &mut Window) -> Result<()> {
+        for (line, cond) in &self.debugger.breakpoints {
+            let y = match self.line_y(*line) {
+                Some(y) => y,
+                None => continue,
+            };
+            let col = if cond.is_some() {
+                Color{r:255./255., g:140./255., b:0./255., a:255./255.}
+            } else {
+                Color{r:200./255., g:0./255., b:0./255., a:255./255.}
+            };
+            window.draw_ex(&
+                Circle::new((3., y), 2.),
+                Col(col),
+                Transform::scale(Vector::new(1., 1.)),
+                3,
+            );
+        }
+        Ok(())
+    }
+
+    fn draw_lints(&self, window: &mut Window) -> Result<()> {
+        let warning = Color{r:255./255., g:140./255., b:0./255., a:255./255.};
+        for lint in &self.lints {
+            let y = match self.line_y(lint.line) {
+                Some(y) => y,
+                None => continue,
+            };
+            window.draw_ex(&
+                Rectangle::new((LINT_X, y - LINE_HEIGHT * 0.5), (3., LINE_HEIGHT)),
+                Col(warning),
+                Transform::scale(Vector::new(1., 1.)),
+                3,
+            );
+            // lints can be left over from before an edit that removed their line
+            if let Some(line) = self.editor.lines().get(lint.line) {
+                let start = line.len() - line.trim_start().len();
+                self.draw_underline(window, lint.line, start, line.trim_end().len(), warning);
+            }
+        }
+        for (line, e) in &self.syntax_errors {
+            self.draw_underline(window, *line, e.start, e.end, Color{r:200./255., g:0./255., b:0./255., a:255./255.});
+        }
+        Ok(())
+    }
+
+    /// underlines columns `start..end`, at least one character wide, as far as they are in view
+    fn draw_underline(&self, window: &mut Window, line: usize, start: usize, end: usize, col: Color) {
+        let y = match self.line_y(line) {
+            Some(y) => y,
+            None => return,
+        };
+        let start = start.max(self.scroll.col);
+        let end = end.max(start + 1).min(self.scroll.col + VIEW_COLS);
+        if start >= end { return }
+        window.draw_ex(&
+            Rectangle::new(
+                (self.col_x(start) - CHAR_WIDTH * 0.5, y + LINE_HEIGHT * 0.5 - 1.),
+                (CHAR_WIDTH * (end - start) as f32, 1.)
+            ),
+            Col(col),
+            Transform::scale(Vector::new(1., 1.)),
+            3,
+        );
+    }
+
+    /// draws the prompt if one is open, otherwise the last debugger message and the watch list
+    fn draw_status(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let text = match &self.prompt {
+            Some(Prompt::Condition(line, text)) => format!("IF@{}: {}{}", line + 1, text, CURSOR),
+            Some(Prompt::Watch(text)) => format!("WATCH: {}{}", text, CURSOR),
+            None => match &self.status {
+                Some(status) => status.clone(),
+                None if self.editor.blocked().is_some() => self.limit_status(self.editor.blocked().unwrap()).unwrap(),
+                None if !self.is_debugging && self.diagnostic().is_some() => {
+                    self.diagnostic().unwrap().to_uppercase()
+                }
+                None if !self.debugger.watches.is_empty() => {
+                    let names: Vec<_> = self.debugger.watches.iter().map(|w| match w {
+                        Watch::Reg(reg) => format!("{:?}", reg),
+                        Watch::Position => "POS".to_owned(),
+                    }).collect();
+                    format!("WATCH: {}", names.join(" "))
+                }
+                None => return Ok(()),
+            }
+        };
+        sprites.execute(|spr|{
+            let img = spr.render_str(&text);
+            // left aligned, the image is scaled around its center
+            let w = img.area().width() * 0.1;
+            window.draw_ex(&
+                img.area().with_center((STATUS_X + w * 0.5, STATUS_Y)),
+                Img(&img),
+                Transform::scale(Vector::new(0.1, 0.1)),
+                1,
+            );
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn draw_timeline(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        if !self.is_debugging { return Ok(()) }
+        let span = self.furthest.max(1) as f32;
+        window.draw_ex(&
+            Rectangle::new((TIMELINE_X, TIMELINE_Y), (TIMELINE_W, TIMELINE_H)),
+            Col(Color{r:120./255., g:120./255., b:120./255., a:255./255.}),
+            Transform::scale(Vector::new(1., 1.)),
+            1,
+        );
+        // the part of the run that can still be stepped back into
+        let first = self.history.first_cycle().unwrap_or(self.sim.cycle) as f32;
+        window.draw_ex(&
+            Rectangle::new(
+                (TIMELINE_X + TIMELINE_W * first / span, TIMELINE_Y),
+                (TIMELINE_W * (self.furthest as f32 - first) / span, TIMELINE_H)
+            ),
+            Col(Color{r:255./255., g:221./255., b:0./255., a:255./255.}),
+            Transform::scale(Vector::new(1., 1.)),
+            2,
+        );
+        window.draw_ex(&
+            Rectangle::new(
+                (TIMELINE_X + TIMELINE_W * self.sim.cycle as f32 / span - 1., TIMELINE_Y - 2.),
+                (2., TIMELINE_H + 4.)
+            ),
+            Col(Color{r:0./255., g:0./255., b:0./255., a:255./255.}),
+            Transform::scale(Vector::new(1., 1.)),
+            3,
+        );
+        sprites.execute(|spr|{
+            let img = spr.render_str(&format!("Cycle: {}/{}", self.sim.cycle, self.furthest));
+            window.draw_ex(&
+                img.area().with_center((TIMELINE_X + TIMELINE_W + 30., TIMELINE_Y + TIMELINE_H * 0.5)),
+                Img(&img),
+                Transform::scale(Vector::new(0.1, 0.1)),
+                1,
+            );
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn draw_budget(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let left = self.cycle_limit().saturating_sub(self.sim.cycle);
+        sprites.execute(|spr|{
+            let img = spr.render_str(&format!("Cycles left: {}", left));
+            window.draw_ex(&
+                img.area().with_center((440., 240.)),
+                Img(&img),
+                Transform::scale(Vector::new(0.1, 0.1)),
+                1,
+            );
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// lines used and characters on the cursor line, against the level's limits if it has them
+    fn draw_limits(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let count = |used: usize, max: usize| if max == usize::MAX { used.to_string() } else { format!("{}/{}", used, max) };
+        let width = self.editor.lines()[self.editor.cursor().line].len();
+        let counters = [
+            (format!("Lines: {}", count(self.editor.lines().len(), self.editor.max_lines)), Limit::Lines, 210.),
+            (format!("Width: {}", count(width, self.editor.max_len)), Limit::Width, 220.),
+        ];
+        for (text, limit, y) in counters.iter() {
+            let color = if self.editor.blocked() == Some(*limit) {
+                Color{r:200./255., g:0./255., b:0./255., a:255./255.}
+            } else {
+                Color::BLACK
+            };
+            sprites.execute(|spr|{
+                let img = spr.render_colored(text, color);
+                window.draw_ex(&
+                    img.area().with_center((440., *y)),
+                    Img(&img),
+                    Transform::scale(Vector::new(0.1, 0.1)),
+                    1,
+                );
+                Ok(())
+            })?;
+        }
         Ok(())
     }
 
-    fn draw_level(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+    fn draw_variants(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        if self.results.len() < 2 { return Ok(()) }
+        let marks: Vec<_> = self.results.iter().enumerate().map(|(i, result)| {
+            let mark = match result {
+                Some(true) => "OK",
+                Some(false) => "X",
+                None if i == self.variant && self.is_debugging => ">",
+                None => "-",
+            };
+            format!("{}{}", i + 1, mark)
+        }).collect();
         sprites.execute(|spr|{
-            let img = spr.render_str(&format!("Level: {}/{}", self.current_level + 1, self.levels.len()));
+            let img = spr.render_str(&format!("TESTS {}", marks.join(" ")));
             window.draw_ex(&
-                img.area().with_center((448., 250.)),
+                img.area().with_center((440., 230.)),
                 Img(&img),
                 Transform::scale(Vector::new(0.1, 0.1)),
                 1,
@@ -162,7 +1030,7 @@ impl Game {
     }
 
     fn draw_grid_items(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
-        for (i, row) in self.current_grid.iter().enumerate() {
+        for (i, row) in self.sim.grid.iter().enumerate() {
             for (j, col) in row.iter().enumerate() {
 
                 let crabloc = (
@@ -188,10 +1056,10 @@ impl Game {
     }
 
     fn draw_error(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
-        if let Some(line) = self.error {
+        if let Some(y) = self.error.and_then(|line| self.line_y(line)) {
             window.draw_ex(&
                 Rectangle::new(
-                    (TEXT_EDITOR_X - 8., TEXT_EDITOR_Y + LINE_HEIGHT * line as f32 - LINE_HEIGHT * 0.5),
+                    (TEXT_EDITOR_X - 8., y - LINE_HEIGHT * 0.5),
                     (100.-7., LINE_HEIGHT)
                 ),
                 Col(Color{r:0./255., g:0./255., b:255./255., a:255./255.}),
@@ -203,8 +1071,11 @@ impl Game {
     }
     fn draw_debugger(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
         if !self.is_debugging{ return Ok(()) }
-        let ip = self.crab.ip;
-        let loc = (TEXT_EDITOR_X - 8., TEXT_EDITOR_Y + LINE_HEIGHT * ip as f32);
+        let y = match self.line_y(self.debug_line()) {
+            Some(y) => y,
+            None => return Ok(()),
+        };
+        let loc = (TEXT_EDITOR_X - 8., y);
         sprites.execute(|spr|{
 
             let pointer = spr.get_img("pointer").unwrap();
@@ -215,14 +1086,16 @@ impl Game {
                 2,
             );
 
-            let col = if self.code_finished {
+            let col = if self.runtime_error.is_some() {
+                Color{r:200./255., g:0./255., b:0./255., a:255./255.}
+            } else if self.code_finished {
                 Color{r:22./255., g:94./255., b:0./255., a:255./255.}
             } else {
                 Color{r:255./255., g:221./255., b:0./255., a:255./255.}
             };
             window.draw_ex(&
                 Rectangle::new(
-                    (TEXT_EDITOR_X - 8., TEXT_EDITOR_Y + LINE_HEIGHT * ip as f32 - LINE_HEIGHT * 0.5),
+                    (TEXT_EDITOR_X - 8., y - LINE_HEIGHT * 0.5),
                     (100.-7., LINE_HEIGHT)
                 ),
                 Col(col),
@@ -236,10 +1109,9 @@ impl Game {
     }
 
     fn draw_registers(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
-        let regs = [Register::A, Register::M, Register::H, Register::V, Register::R];
-        for (i, reg) in regs.iter().enumerate() {
+        for (i, reg) in REGISTERS.iter().enumerate() {
             let loc = (REG_X + REG_OFFSET * i as f32, REG_Y);
-            let val = self.crab.get_reg(*reg);
+            let val = self.sim.crab.get_reg(*reg);
             sprites.execute(|spr|{
                 let img = spr.render_str(&format!("{:?}:{}", reg, val));
                 window.draw_ex(&
@@ -254,12 +1126,71 @@ impl Game {
         Ok(())
     }
 
+    /// input with the next value marked, output so far and the expected output
+    fn draw_streams(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let crab = &self.sim.crab;
+        if crab.input.is_empty() && self.sim.expected.is_empty() { return Ok(()) }
+        let join = |values: &[i32]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ");
+        let input: Vec<_> = crab.input.iter().enumerate()
+            .map(|(i, v)| if i == crab.input_pos { format!(">{}", v) } else { v.to_string() })
+            .collect();
+        let lines = [
+            format!("IN  {}", input.join(" ")),
+            format!("OUT {}", join(&crab.output)),
+            format!("EXP {}", join(&self.sim.expected)),
+        ];
+        for (i, text) in lines.iter().enumerate() {
+            sprites.execute(|spr|{
+                let img = spr.render_str(text);
+                let w = img.area().width() * 0.1;
+                window.draw_ex(&
+                    img.area().with_center((STREAM_X + w * 0.5, STREAM_Y + LINE_HEIGHT * i as f32)),
+                    Img(&img),
+                    Transform::scale(Vector::new(0.1, 0.1)),
+                    1,
+                );
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// the trail while a program runs, fading with age, and the ghost path while none does
+    fn draw_paths(&self, window: &mut Window) {
+        let segments: Vec<_> = if self.is_debugging {
+            self.trail.windows(2).map(|pair| {
+                let age = self.sim.cycle.saturating_sub(pair[1].0) as f32;
+                let alpha = 0.8 * (1. - age / TRAIL_FADE);
+                (pair[0].1, pair[1].1, Color{r:200./255., g:90./255., b:40./255., a:alpha})
+            }).filter(|(_, _, col)| col.a > 0.).collect()
+        } else {
+            self.ghost.windows(2).map(|pair| (pair[0], pair[1], Color{r:60./255., g:110./255., b:220./255., a:0.5})).collect()
+        };
+        for (from, to, col) in segments {
+            window.draw_ex(&
+                Line::new(tile_center(from), tile_center(to)).with_thickness(2.),
+                Col(col),
+                Transform::scale(Vector::new(1., 1.)),
+                1,
+            );
+        }
+        // where the program would leave the crab
+        if let (false, Some(end)) = (self.is_debugging, self.ghost.last()) {
+            window.draw_ex(&
+                Circle::new(tile_center(*end), 3.),
+                Col(Color{r:60./255., g:110./255., b:220./255., a:0.5}),
+                Transform::scale(Vector::new(1., 1.)),
+                1,
+            );
+        }
+    }
+
     fn draw_crab(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
         let crabloc = (
-            ORIGIN_X + self.crab.pos_x as f32 * TILE_X + self.crab.pos_y as f32 * OFFSET,
-            ORIGIN_Y + self.crab.pos_y as f32 * TILE_Y,
+            ORIGIN_X + self.sim.crab.pos_x as f32 * TILE_X + self.sim.crab.pos_y as f32 * OFFSET,
+            ORIGIN_Y + self.sim.crab.pos_y as f32 * TILE_Y,
         );
-        let crab_normal = self.crab.get_reg(Register::R);
+        let crab_normal = self.sim.crab.get_reg(Register::R);
         let anim_name = match crab_normal {
             0 => "crab-rest",
             1 => "crab-left",
@@ -281,108 +1212,612 @@ impl Game {
     }
 
     fn draw_text(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let (first, last) = (self.scroll.col, self.scroll.col + VIEW_COLS);
+        if let Some((start, end)) = self.editor.selection() {
+            for line in start.line..=end.line {
+                let y = match self.line_y(line) {
+                    Some(y) => y,
+                    None => continue,
+                };
+                let from = if line == start.line { start.col } else { 0 };
+                // a selected line break shows as one more cell
+                let to = if line == end.line { end.col } else { self.editor.lines()[line].len() + 1 };
+                let (from, to) = (from.max(first), to.min(last));
+                if from >= to { continue }
+                window.draw_ex(&
+                    Rectangle::new(
+                        (self.col_x(from) - CHAR_WIDTH * 0.5, y - LINE_HEIGHT * 0.5),
+                        (CHAR_WIDTH * (to - from) as f32, LINE_HEIGHT)
+                    ),
+                    Col(Color{r:120./255., g:170./255., b:255./255., a:255./255.}),
+                    Transform::scale(Vector::new(1., 1.)),
+                    1,
+                );
+            }
+        }
+        let cursor = self.editor.cursor();
+        if let Some(y) = self.line_y(cursor.line) {
+            window.draw_ex(&
+                Rectangle::new((self.col_x(cursor.col) - CHAR_WIDTH * 0.5, y - LINE_HEIGHT * 0.5), (1., LINE_HEIGHT)),
+                Col(Color::BLACK),
+                Transform::scale(Vector::new(1., 1.)),
+                3,
+            );
+        }
+        self.draw_scrollbar(window);
         sprites.execute(|spr|{
-            let mut row = 0.;
-            let mut col = 0.;
-            for i in self.buf.chars() {
-                if i == ' ' {
-                    col += 1.;
-                    continue;
-                } else if i == '\n' {
-                    row += 1.;
-                    col = 0.;
-                    continue;
+            for (row, line) in self.editor.lines().iter().enumerate() {
+                let y = match self.line_y(row) {
+                    Some(y) => y,
+                    None => continue,
+                };
+                for token in syntax::tokenize(line) {
+                    let color = token_color(token.kind);
+                    for (i, ch) in token.text.chars().enumerate() {
+                        let col = token.start + i;
+                        if ch == ' ' || col < first || col >= last { continue }
+                        let ltr = spr.render_colored(&ch.to_string(), color);
+                        window.draw_ex(&
+                            ltr.area().with_center((self.col_x(col), y)),
+                            Img(&ltr),
+                            Transform::scale(Vector::new(0.1, 0.1)),
+                            2,
+                        );
+                    }
                 }
-                let ltr = spr.render_str(&format!("{}", i));
-                let loc = (TEXT_EDITOR_X + CHAR_WIDTH * col, TEXT_EDITOR_Y + LINE_HEIGHT * row);
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// the suggestions under the word being typed, instructions with their operands
+    fn draw_completion(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let completion = match self.completion() {
+            Some(completion) => completion,
+            None => return Ok(()),
+        };
+        let cursor = self.editor.cursor();
+        let y = match self.line_y(cursor.line) {
+            Some(y) => y,
+            None => return Ok(()),
+        };
+        let mut rows: Vec<_> = completion.signature.iter().cloned().collect();
+        let first = rows.len();
+        rows.extend(completion.words.iter().take(SUGGESTIONS).map(|word| match &completion.signature {
+            Some(_) => word.clone(),
+            None => syntax::signature(word).unwrap_or_else(|| word.clone()),
+        }));
+        let x = self.col_x(completion.start) - CHAR_WIDTH * 0.5;
+        sprites.execute(|spr|{
+            let imgs: Vec<_> = rows.iter().map(|row| spr.render_str(row)).collect();
+            let w = imgs.iter().map(|img| img.area().width() * 0.1).fold(0., f32::max) + 2.;
+            for (i, img) in imgs.iter().enumerate() {
+                let row_y = y + LINE_HEIGHT * (i + 1) as f32;
+                // the suggestion tab accepts is highlighted
+                let bg = if i == first {
+                    Color{r:120./255., g:170./255., b:255./255., a:255./255.}
+                } else {
+                    Color{r:235./255., g:235./255., b:220./255., a:255./255.}
+                };
+                window.draw_ex(&
+                    Rectangle::new((x, row_y - LINE_HEIGHT * 0.5), (w, LINE_HEIGHT)),
+                    Col(bg),
+                    Transform::scale(Vector::new(1., 1.)),
+                    4,
+                );
+                let img_w = img.area().width() * 0.1;
                 window.draw_ex(&
-                    ltr.area().with_center(loc),
-                    Img(&ltr),
+                    img.area().with_center((x + 1. + img_w * 0.5, row_y)),
+                    Img(img),
                     Transform::scale(Vector::new(0.1, 0.1)),
-                    2,
+                    5,
                 );
-                col += 1.;
             }
             Ok(())
         })?;
         Ok(())
     }
+
+    /// the key bindings in two columns, where the reference goes
+    fn draw_key_help(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let lines = match &self.key_help {
+            Some(lines) => lines,
+            None => return Ok(()),
+        };
+        let rows = lines.len().div_ceil(2);
+        sprites.execute(|spr|{
+            for (i, line) in lines.iter().enumerate() {
+                let img = spr.render_str(line);
+                let w = img.area().width() * 0.1;
+                let x = REFERENCE_X + 3. + REFERENCE_W * 0.5 * (i / rows) as f32;
+                let y = REFERENCE_Y + 2. + LINE_HEIGHT * ((i % rows) as f32 + 0.5);
+                window.draw_ex(&
+                    img.area().with_center((x + w * 0.5, y)),
+                    Img(&img),
+                    Transform::scale(Vector::new(0.1, 0.1)),
+                    8,
+                );
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// the list of topics on the left and the open one on the right
+    fn draw_reference(&self, window: &mut Window, sprites: &mut Asset<Sprites>) -> Result<()> {
+        let selected = match self.reference {
+            Some(selected) => selected,
+            None => return Ok(()),
+        };
+        let row_y = |row: usize| REFERENCE_Y + 2. + LINE_HEIGHT * (row as f32 + 0.5);
+        window.draw_ex(&
+            Rectangle::new((REFERENCE_X, row_y(selected) - LINE_HEIGHT * 0.5), (TOPICS_W, LINE_HEIGHT)),
+            Col(Color{r:120./255., g:170./255., b:255./255., a:255./255.}),
+            Transform::scale(Vector::new(1., 1.)),
+            7,
+        );
+        let topic = &self.topics[selected];
+        let mut rows: Vec<(String, Color)> = vec![(topic.title.clone(), token_color(TokenKind::Mnemonic)), (String::new(), Color::BLACK)];
+        rows.extend(help::wrap(&topic.text, REFERENCE_COLS).into_iter().map(|line| (line, Color::BLACK)));
+        if !topic.example.is_empty() {
+            rows.push((String::new(), Color::BLACK));
+            rows.push(("Example:".to_owned(), Color::BLACK));
+            rows.extend(topic.example.lines().map(|line| (format!("  {}", line), token_color(TokenKind::Comment))));
+        }
+        let text_x = REFERENCE_X + TOPICS_W + 6.;
+        sprites.execute(|spr|{
+            let mut imgs = vec![];
+            for (i, topic) in self.topics.iter().enumerate() {
+                imgs.push((REFERENCE_X + 3., row_y(i), spr.render_str(&topic.name)));
+            }
+            for (i, (row, color)) in rows.iter().enumerate() {
+                imgs.push((text_x, row_y(i), spr.render_colored(row, *color)));
+            }
+            for (x, y, img) in imgs.iter() {
+                let w = img.area().width() * 0.1;
+                window.draw_ex(&
+                    img.area().with_center((x + w * 0.5, *y)),
+                    Img(img),
+                    Transform::scale(Vector::new(0.1, 0.1)),
+                    8,
+                );
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// a bar along the right edge of the editor while the program is longer than it
+    fn draw_scrollbar(&self, window: &mut Window) {
+        let lines = self.editor.lines().len();
+        if lines <= VIEW_LINES { return }
+        let height = LINE_HEIGHT * VIEW_LINES as f32;
+        let top = TEXT_EDITOR_Y - LINE_HEIGHT * 0.5;
+        window.draw_ex(&
+            Rectangle::new(
+                (LINT_X + 4., top + height * self.scroll.line as f32 / lines as f32),
+                (2., height * VIEW_LINES as f32 / lines as f32)
+            ),
+            Col(Color{r:110./255., g:110./255., b:110./255., a:255./255.}),
+            Transform::scale(Vector::new(1., 1.)),
+            3,
+        );
+    }
+}
+
+/// center of a tile of the grid on screen
+fn tile_center((x, y): (i32, i32)) -> Vector {
+    Vector::new(
+        ORIGIN_X + x as f32 * TILE_X + y as f32 * OFFSET,
+        ORIGIN_Y + y as f32 * TILE_Y,
+    )
+}
+
+fn token_color(kind: TokenKind) -> Color {
+    match kind {
+        TokenKind::Mnemonic => Color{r:0./255., g:60./255., b:160./255., a:255./255.},
+        TokenKind::Register => Color{r:130./255., g:0./255., b:140./255., a:255./255.},
+        TokenKind::Number => Color{r:170./255., g:80./255., b:0./255., a:255./255.},
+        TokenKind::Label => Color{r:22./255., g:94./255., b:0./255., a:255./255.},
+        TokenKind::Comment => Color{r:110./255., g:110./255., b:110./255., a:255./255.},
+        TokenKind::Error => Color{r:200./255., g:0./255., b:0./255., a:255./255.},
+    }
 }
 
 impl Game {
     fn load_code(&mut self) {
-        let code = self.buf.replace(CURSOR, "");
-        if let Err(line) = self.crab.load_code(&code) {
+        // a program can only break the limits if it was loaded from elsewhere
+        let lines = self.editor.lines();
+        if lines.len() > self.editor.max_lines {
+            self.error = Some(self.editor.max_lines);
+            self.status = self.limit_status(Limit::Lines);
+        } else if let Some(line) = lines.iter().position(|l| l.len() > self.editor.max_len) {
             self.error = Some(line);
+            self.status = self.limit_status(Limit::Width);
+        } else if let Err(line) = self.sim.load_code(&self.editor.text()) {
+            self.error = Some(line);
+        }
+        if let Some(line) = self.error {
+            self.show_line(line);
         }
     }
 
     fn objective_completed(&self) -> bool {
-        for i in &self.current_grid {
-            for j in i {
-                if *j { return false; }
-            }
-        }
-        return true;
+        self.sim.cleared()
     }
 
-    fn next_level(&mut self, sprites: &mut Asset<Sprites>) {
+    /// moves on to the next level, or starts generating it and returns false
+    fn next_level(&mut self, sprites: &mut Asset<Sprites>) -> bool {
         self.code_finished = true;
+        // past the hand made levels there is always another generated one, unless the generator fails
+        if self.current_level + 1 == self.levels.len() {
+            if let Some(e) = &self.no_next_level {
+                self.status = Some(format!("NO NEXT LEVEL: {}", e).to_uppercase());
+                return false;
+            }
+            if self.generation.is_none() {
+                match Generation::new(self.levels.len() as u64, &Params::default()) {
+                    Ok(generation) => self.generation = Some(generation),
+                    Err(e) => {
+                        self.status = Some(format!("NO NEXT LEVEL: {}", e).to_uppercase());
+                        self.no_next_level = Some(e);
+                        return false;
+                    }
+                }
+            }
+            self.status = Some("GENERATING THE NEXT LEVEL".to_owned());
+            return false;
+        }
         self.current_level += 1;
-        self.current_grid = self.levels[self.current_level].clone();
+        self.unlocked = self.unlocked.max(self.current_level);
         self.stop();
         sprites.execute(|i| {
             i.get_sound("success").unwrap().play()?;
             Ok(())
         }).unwrap();
+        true
+    }
+
+    /// runs the generation of the next level for a while, moving on to it if the last level is cleared
+    fn poll_generation(&mut self, sprites: &mut Asset<Sprites>) {
+        let result = match &mut self.generation {
+            Some(generation) => match generation.run(GENERATION_STATES) {
+                Some(result) => result,
+                None => return,
+            },
+            None => return,
+        };
+        self.generation = None;
+        let waiting = self.current_level + 1 == self.levels.len() && self.last_variant() && self.objective_completed();
+        match result {
+            Ok(level) => {
+                self.levels.push(level);
+                if waiting {
+                    self.next_level(sprites);
+                }
+            }
+            Err(e) => {
+                if waiting {
+                    self.status = Some(format!("NO NEXT LEVEL: {}", e).to_uppercase());
+                }
+                self.no_next_level = Some(e);
+            }
+        }
+    }
+
+    /// moves on to the next variant, keeping the loaded code
+    fn next_variant(&mut self) {
+        self.variant += 1;
+        self.history.clear();
+        self.furthest = 0;
+        self.sim.reset(self.levels[self.current_level].grids[self.variant].clone());
+        self.trail.clear();
+        self.mark_trail();
+        self.status = Some(format!("VARIANT {}/{}", self.variant + 1, self.results.len()));
+    }
+
+    fn last_variant(&self) -> bool {
+        self.variant + 1 == self.results.len()
     }
 
     pub fn step(&mut self, sprites: &mut Asset<Sprites>) {
         if self.objective_completed() {
-            self.next_level(sprites);
+            self.results[self.variant] = Some(true);
+            if !self.last_variant() {
+                self.next_variant();
+                return;
+            }
+            if !self.next_level(sprites) {
+                return;
+            }
         }
         if !self.is_debugging {
             self.is_debugging = true;
             self.load_code();
+            self.mark_trail();
             return;
         }
         if self.error.is_some()  {
             return;
         }
-        if let Err(_) = self.crab.step() {
+        if self.sim.crab.stopped() {
+            self.code_finished = true;
+            self.results[self.variant] = Some(false);
+            self.status = Some(format!("HALTED AFTER {} CYCLES", self.sim.cycle));
+            return;
+        }
+        let limit = self.cycle_limit();
+        if self.sim.cycle >= limit {
             self.code_finished = true;
+            self.results[self.variant] = Some(false);
+            self.status = Some(format!("TIMEOUT AFTER {} CYCLES", limit));
+            return;
+        }
+        self.status = None;
+        self.history.push(Snapshot {
+            cycle: self.sim.cycle,
+            crab: self.sim.crab.save(),
+            grid: self.sim.grid.clone(),
+            code_finished: self.code_finished,
+        });
+        if let Err(e) = self.sim.step() {
+            self.code_finished = true;
+            self.results[self.variant] = Some(false);
+            self.status = Some(format!("ERROR: {}", e).to_uppercase());
+            self.runtime_error = Some(e);
+        }
+        self.mark_trail();
+        self.furthest = self.furthest.max(self.sim.cycle);
+        self.show_line(self.debug_line());
+    }
+
+    /// the line the debugger marks, which after a crash is the one that failed rather than where the crab stopped
+    fn debug_line(&self) -> usize {
+        self.runtime_error.as_ref().map_or(self.sim.crab.ip, RuntimeError::line)
+    }
+
+    fn cycle_limit(&self) -> usize {
+        self.levels[self.current_level].cycle_limit
+    }
+
+    /// undoes the last cycle
+    pub fn step_back(&mut self) {
+        if !self.is_debugging { return }
+        self.is_playing = false;
+        if let Some(snapshot) = self.history.pop() {
+            self.restore(snapshot);
+        }
+    }
+
+    /// moves the simulation to the given cycle, replaying forward if needed
+    ///
+    /// going back is limited to what is still kept in the history
+    pub fn goto_cycle(&mut self, cycle: usize, sprites: &mut Asset<Sprites>) {
+        if !self.is_debugging || self.error.is_some() { return }
+        self.is_playing = false;
+        if cycle < self.sim.cycle {
+            let cycle = cycle.max(self.history.first_cycle().unwrap_or(self.sim.cycle));
+            if let Some(snapshot) = self.history.rewind_to(cycle) {
+                self.restore(snapshot);
+            }
+            return;
         }
-        let i = self.crab.pos_y as usize;
-        let j = self.crab.pos_x as usize;
-        if let Some(Some(pos)) = self.current_grid.get_mut(i).map(|row|row.get_mut(j)) {
-            *pos = false;
+        let (level, variant) = (self.current_level, self.variant);
+        while self.sim.cycle < cycle && !self.sim.crab.stopped() && (level, variant) == (self.current_level, self.variant) {
+            let before = self.sim.cycle;
+            self.step(sprites);
+            // timed out
+            if self.sim.cycle == before { break }
+        }
+    }
+
+    /// maps a point on the timeline to a cycle, None if the point is outside of it
+    pub fn timeline_cycle(&self, x: f32, y: f32) -> Option<usize> {
+        if !self.is_debugging { return None }
+        if !(TIMELINE_X..=TIMELINE_X + TIMELINE_W).contains(&x) || !(TIMELINE_Y - 4. ..=TIMELINE_Y + TIMELINE_H + 4.).contains(&y) {
+            return None;
         }
-        self.crab.sensor();
+        let t = (x - TIMELINE_X) / TIMELINE_W;
+        Some((t * self.furthest as f32).round() as usize)
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.sim.cycle = snapshot.cycle;
+        self.sim.crab.restore(&snapshot.crab);
+        self.sim.set_grid(snapshot.grid);
+        self.runtime_error = None;
+        if let Some(trace) = self.sim.trace.as_mut() {
+            trace.truncate(snapshot.cycle);
+        }
+        let now = self.sim.cycle;
+        self.trail.retain(|(cycle, _)| *cycle <= now);
+        self.code_finished = snapshot.code_finished;
+        self.show_line(self.sim.crab.ip);
+    }
+
+    pub fn cycle_speed(&mut self) {
+        self.speed = self.speed.next();
+    }
+
+    /// runs instantly until the instruction on the cursor line is next
+    pub fn run_to_cursor(&mut self, sprites: &mut Asset<Sprites>) {
+        let line = self.cursor_line();
+        self.run_until(sprites, Some(line));
+    }
+
+    /// runs instantly until the program ends or the level is cleared
+    pub fn fast_forward(&mut self, sprites: &mut Asset<Sprites>) {
+        self.run_until(sprites, None);
+    }
+
+    fn run_until(&mut self, sprites: &mut Asset<Sprites>, stop_at: Option<usize>) {
+        self.is_playing = false;
+        if !self.is_debugging {
+            // loads the code
+            self.step(sprites);
+        }
+        if self.error.is_some() { return }
+        self.code_finished = false;
+        // cleared variants are passed through, only the last one ends the run
+        while !self.code_finished {
+            if self.objective_completed() && self.last_variant() { break }
+            self.step(sprites);
+            if Some(self.sim.crab.ip) == stop_at { break }
+        }
+    }
+
+    /// starts recording a trace, or writes the recorded trace to a file in the working directory
+    pub fn toggle_trace(&mut self, format: TraceFormat) {
+        match self.sim.trace.take() {
+            None => {
+                self.sim.trace = Some(Trace::new());
+                self.status = Some("TRACE ON".to_owned());
+            }
+            Some(trace) => {
+                let path = format!("trace.{}", format.extension());
+                let res = File::create(&path).and_then(|mut f| trace.write(&mut f, format));
+                self.status = Some(match res {
+                    Ok(_) => format!("WROTE {}", path).to_uppercase(),
+                    Err(e) => format!("TRACE: {}", e).to_uppercase(),
+                });
+            }
+        }
+    }
+
+    /// runs the current program headlessly and saves it as a replay
+    pub fn save_replay(&mut self) {
+        let code = self.editor.text();
+        let res = Replay::record(&(self.current_level + 1).to_string(), &code)
+            .and_then(|replay| std::fs::write(REPLAY_FILE, replay.to_string()).map_err(|e| e.to_string()));
+        self.status = Some(match res {
+            Ok(_) => format!("WROTE {}", REPLAY_FILE),
+            Err(e) => format!("REPLAY: {}", e),
+        }.to_uppercase());
+    }
+
+    /// loads the saved replay into the editor and plays it on its level
+    pub fn load_replay(&mut self) {
+        let res = std::fs::read_to_string(REPLAY_FILE)
+            .map_err(|e| e.to_string())
+            .and_then(|file| file.parse::<Replay>())
+            .and_then(|replay| self.play_replay(&replay));
+        if let Err(e) = res {
+            self.status = Some(format!("REPLAY: {}", e).to_uppercase());
+        }
+    }
+
+    fn play_replay(&mut self, replay: &Replay) -> std::result::Result<(), String> {
+        let idx = replay.level.parse::<usize>().ok()
+            .and_then(|n| n.checked_sub(1))
+            .filter(|i| *i < self.levels.len())
+            .ok_or(format!("level {} is not in the game", replay.level))?;
+        if level::hash(&self.levels[idx]) != replay.level_hash {
+            return Err(format!("level {} changed since the replay was recorded", replay.level));
+        }
+        self.current_level = idx;
+        self.stop();
+        self.editor.set_text(replay.program.trim_end());
+        self.scroll_to(self.editor.cursor());
+        self.relint();
+        self.play();
+        self.status = Some(format!("REPLAY: {}", sim::describe(&replay.outcomes)).to_uppercase());
+        Ok(())
+    }
+
+    /// starts looking for a program that clears the current level, `poll_hint` loads it once found
+    pub fn show_solution(&mut self) {
+        let level = &self.levels[self.current_level];
+        let search = Synthesis::new(level, HINT_LEN, Goal::Shortest, level.cycle_limit);
+        self.hint = Some((self.current_level, Hint::Loop(Box::new(search))));
+        self.status = Some("SEARCHING FOR A SOLUTION".to_owned());
+    }
+
+    /// runs the search of `show_solution` for a while and replaces the program once it is done
+    fn poll_hint(&mut self) {
+        let (idx, hint) = match self.hint.take() {
+            Some(hint) => hint,
+            None => return,
+        };
+        if idx != self.current_level {
+            return;
+        }
+        let level = &self.levels[idx];
+        let (max_lines, max_len) = (self.editor.max_lines, self.editor.max_len);
+        let fits = |program: &String| program.lines().count() <= max_lines && program.lines().all(|l| l.len() <= max_len);
+        let program = match hint {
+            Hint::Loop(mut search) => {
+                if !search.run(HINT_CYCLES) {
+                    self.hint = Some((idx, Hint::Loop(search)));
+                    return;
+                }
+                let found = search.best().map(|found| found.program.clone()).filter(fits);
+                match (found, level.grids.as_slice()) {
+                    (Some(program), _) => Some(program),
+                    (None, [grid]) if solver::supports(level) => {
+                        if let Ok(search) = solver::Search::new(grid, Profile::full(), HINT_STATES) {
+                            self.hint = Some((idx, Hint::Straight(search)));
+                            return;
+                        }
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            Hint::Straight(mut search) => match search.run(HINT_FRAME_STATES) {
+                Some(verdict) => verdict.program().filter(fits),
+                None => {
+                    self.hint = Some((idx, Hint::Straight(search)));
+                    return;
+                }
+            },
+        };
+        match program {
+            Some(program) => {
+                self.stop();
+                self.editor.set_text(&program);
+                self.scroll_to(self.editor.cursor());
+                self.relint();
+                self.status = Some("SOLUTION LOADED".to_owned());
+            }
+            None => self.status = Some("NO SHORT SOLUTION FOUND".to_owned()),
+        }
+    }
+
+    /// switches to a level that has been reached, dropping the run but keeping the program
+    fn select_level(&mut self, level: usize) {
+        if level > self.unlocked { return }
+        self.current_level = level;
+        self.stop();
     }
 
     pub fn stop(&mut self) {
         self.is_debugging = false;
         self.is_playing = false;
-        self.crab.reset();
-        self.current_grid = self.levels[self.current_level].clone();
+        self.furthest = 0;
+        self.history.clear();
+        self.status = None;
+        self.runtime_error = None;
+        self.variant = 0;
+        self.results = vec![None; self.levels[self.current_level].grids.len()];
+        let level = &self.levels[self.current_level];
+        self.sim.set_streams(level.input.clone(), level.output.clone());
+        self.sim.reset(level.grids[0].clone());
+        self.editor.max_lines = level.max_lines.unwrap_or(usize::MAX);
+        self.editor.max_len = level.max_len.unwrap_or(usize::MAX);
         self.error = None;
         self.code_finished = true;
+        self.trail.clear();
+        self.predict();
     }
 
+    /// starts playing, or pauses if already playing
     pub fn play(&mut self) {
+        if self.is_playing {
+            self.is_playing = false;
+            return;
+        }
         self.is_playing = true;
         self.code_finished = false;
     }
 }
-
-fn to_grid(file: &str) -> Grid {
-    let mut grid = vec![];
-    for line in file.lines().take(GRID_H) {
-        let mut temp = vec![];
-        for ch in line.chars().take(GRID_W) {
-            temp.push(ch=='x');
-        }
-        grid.push(temp);
-    }
-    grid
-}
\ No newline at end of file
@@ -0,0 +1,176 @@
+//! builds random levels from a seed, each one checked with the solver
+
+use crate::level::{Level, Grid, GRID_H, GRID_W};
+use crate::solver::{Profile, Search, Verdict};
+
+/// random layouts tried before giving up
+const ATTEMPTS: usize = 1000;
+/// states the solver may explore per layout
+const MAX_STATES: usize = 500_000;
+/// the cycle limit of a generated level is this many times its minimum
+const SLACK: usize = 4;
+
+/// splitmix64, small and good enough to lay out levels
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// a number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// the knobs of the generator
+///
+/// the game has no walls, so unlike the other layout knobs there is no wall density
+#[derive(Debug, Clone, PartialEq)]
+pub struct Params {
+    /// size of the area the targets are placed in, at most the size of the grid
+    pub width: usize,
+    pub height: usize,
+    pub targets: usize,
+    /// fewest `RCW` and `RCC` any solution must execute
+    pub rotations: u32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params { width: GRID_W, height: GRID_H, targets: 5, rotations: 1 }
+    }
+}
+
+fn layout(rng: &mut Rng, params: &Params) -> Grid {
+    let (left, top) = (rng.below(GRID_W - params.width + 1), rng.below(GRID_H - params.height + 1));
+    let mut cells: Vec<_> = (0..params.height)
+        .flat_map(|y| (0..params.width).map(move |x| (left + x, top + y)))
+        .collect();
+    let mut grid = vec![vec![false; GRID_W]; GRID_H];
+    for i in 0..params.targets {
+        let j = i + rng.below(cells.len() - i);
+        cells.swap(i, j);
+        let (x, y) = cells[i];
+        grid[y][x] = true;
+    }
+    grid
+}
+
+/// a `generate` that can be run a few solver states at a time, so the game can spread it over frames
+pub struct Generation {
+    rng: Rng,
+    params: Params,
+    attempts: usize,
+    grid: Grid,
+    /// the search on `grid`, None between layouts
+    search: Option<Search>,
+    /// fewest cycles `grid` takes, once known the search checks it needs `params.rotations`
+    cycles: Option<usize>,
+}
+
+impl Generation {
+    pub fn new(seed: u64, params: &Params) -> Result<Generation, String> {
+        if params.width == 0 || params.width > GRID_W || params.height == 0 || params.height > GRID_H {
+            return Err(format!("area must fit in {}x{}", GRID_W, GRID_H));
+        }
+        if params.targets == 0 || params.targets > params.width * params.height {
+            return Err(format!("{} targets do not fit in {}x{}", params.targets, params.width, params.height));
+        }
+        Ok(Generation { rng: Rng::new(seed), params: params.clone(), attempts: 0, grid: vec![], search: None, cycles: None })
+    }
+
+    /// explores up to `states` more solver states, returns the result once there is one
+    pub fn run(&mut self, states: usize) -> Option<Result<Level, String>> {
+        let mut left = states;
+        loop {
+            let search = match &mut self.search {
+                Some(search) => search,
+                None => {
+                    if self.attempts == ATTEMPTS {
+                        return Some(Err(format!("no level found in {} attempts, try other parameters", ATTEMPTS)));
+                    }
+                    self.attempts += 1;
+                    self.grid = layout(&mut self.rng, &self.params);
+                    self.cycles = None;
+                    match Search::new(&self.grid, Profile::full(), MAX_STATES) {
+                        Ok(search) => self.search = Some(search),
+                        Err(e) => return Some(Err(e)),
+                    }
+                    continue;
+                }
+            };
+            if left == 0 {
+                return None;
+            }
+            let before = search.expanded();
+            let verdict = search.run(left).cloned()?;
+            left -= search.expanded() - before;
+            self.search = None;
+            match (self.cycles, verdict) {
+                (None, Verdict::Solvable { cycles, .. }) if self.params.rotations > 0 => {
+                    self.cycles = Some(cycles);
+                    let fewer = Profile { max_turns: Some(self.params.rotations - 1), ..Profile::full() };
+                    match Search::new(&self.grid, fewer, MAX_STATES) {
+                        Ok(search) => self.search = Some(search),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                (None, Verdict::Solvable { cycles, .. }) | (Some(cycles), Verdict::Unsolvable) => {
+                    let grid = std::mem::take(&mut self.grid);
+                    return Some(Ok(Level { grids: vec![grid], cycle_limit: cycles * SLACK, input: vec![], output: vec![], max_lines: None, max_len: None }));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// a solvable level that needs at least `params.rotations` rotations, the same for every `seed`
+pub fn generate(seed: u64, params: &Params) -> Result<Level, String> {
+    let mut generation = Generation::new(seed, params)?;
+    loop {
+        if let Some(level) = generation.run(usize::MAX) {
+            return level;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver;
+
+    #[test]
+    fn test_generate() {
+        let params = Params { targets: 4, rotations: 2, ..Params::default() };
+        let level = generate(7, &params).unwrap();
+        assert_eq!(level, generate(7, &params).unwrap());
+        assert_eq!(level.grids[0].iter().flatten().filter(|t| **t).count(), 4);
+        let one = Profile { max_turns: Some(1), ..Profile::full() };
+        assert_eq!(solver::solve(&level.grids[0], one, MAX_STATES), Ok(Verdict::Unsolvable));
+
+        let mut generation = Generation::new(7, &params).unwrap();
+        let mut runs = 1;
+        let spread = loop {
+            match generation.run(1000) {
+                Some(result) => break result,
+                None => runs += 1,
+            }
+        };
+        assert!(runs > 1);
+        assert_eq!(spread, Ok(level));
+
+        let too_many = Params { width: 2, height: 2, targets: 5, rotations: 0 };
+        assert!(generate(7, &too_many).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! the in-game reference, built from the instruction table
+
+use crate::crab::REGISTERS;
+use crate::isa::INSTRUCTIONS;
+
+/// what each register holds, in the order of `REGISTERS`
+const REGISTER_HELP: [&str; 5] = [
+    "General purpose accumulator. The conditional jumps test it.",
+    "Motor. The crab only crawls sideways, to its right while M is positive and to its left while it is negative. See LAG.",
+    "Sensor. After every cycle it holds the horizontal distance to the nearest tile that is left, or 0 once none is. Anything written to it is overwritten.",
+    "Sensor. After every cycle it holds the vertical distance to the nearest tile that is left, or 0 once none is. Anything written to it is overwritten.",
+    "Rotation modulo 4. 0 is down, 1 is left, 2 is up, 3 is right.",
+];
+
+const MOTOR_LAG: &str = "The motor runs at the start of every instruction, with the value M had after the one before. \
+A crab starts moving one cycle after M is set and keeps going for one cycle after M is cleared.";
+
+const MOTOR_LAG_EXAMPLE: &str = "MOV 1 M   # stands still\nNOP       # moves right\nMOV 0 M   # moves right\nNOP       # stands still";
+
+/// one entry of the reference
+pub struct Topic {
+    /// the word it is listed under
+    pub name: String,
+    /// first line of the entry, such as the instruction with its operands
+    pub title: String,
+    pub text: String,
+    pub example: &'static str,
+}
+
+/// every instruction, then the registers and the motor lag rule
+pub fn topics() -> Vec<Topic> {
+    let instructions = INSTRUCTIONS.iter().map(|instr| Topic {
+        name: instr.mnemonic.to_owned(),
+        title: instr.signature(),
+        text: format!("{} Takes {} cycle{}.", instr.description, instr.cycles, if instr.cycles == 1 { "" } else { "s" }),
+        example: instr.example,
+    });
+    let registers = REGISTERS.iter().zip(REGISTER_HELP.iter()).map(|(reg, text)| Topic {
+        name: format!("{:?}", reg),
+        title: format!("Register {:?}", reg),
+        text: text.to_string(),
+        example: "",
+    });
+    let lag = Topic {
+        name: "LAG".to_owned(),
+        title: "Motor lag".to_owned(),
+        text: MOTOR_LAG.to_owned(),
+        example: MOTOR_LAG_EXAMPLE,
+    };
+    instructions.chain(registers).chain(std::iter::once(lag)).collect()
+}
+
+/// index of the topic for a mnemonic or register name
+pub fn find(topics: &[Topic], word: &str) -> Option<usize> {
+    topics.iter().position(|topic| topic.name == word)
+}
+
+/// breaks text into lines of at most `width` characters, at spaces where it can
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+        while line.len() > width {
+            let rest = line.split_off(width);
+            lines.push(std::mem::replace(&mut line, rest));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topics() {
+        let topics = topics();
+        for instr in INSTRUCTIONS.iter() {
+            assert_eq!(topics[find(&topics, instr.mnemonic).unwrap()].title, instr.signature());
+        }
+        assert_eq!(topics[find(&topics, "M").unwrap()].title, "Register M");
+        assert_eq!(find(&topics, "L"), None);
+
+        assert_eq!(wrap("Jumps to a label if A is 0.", 12), vec!["Jumps to a", "label if A", "is 0."]);
+        assert_eq!(wrap("ABCDEFGH IJ", 4), vec!["ABCD", "EFGH", "IJ"]);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::crab::CrabState;
+use crate::level::Grid;
+use std::collections::VecDeque;
+
+/// number of cycles kept around for stepping back
+pub const HISTORY_LEN: usize = 4096;
+
+/// everything needed to put the simulation back to the start of a cycle
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub cycle: usize,
+    pub crab: CrabState,
+    pub grid: Grid,
+    pub code_finished: bool,
+}
+
+/// bounded record of past cycles, oldest snapshots are dropped first
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.capacity == 0 { return; }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// removes and returns the most recent snapshot
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop_back()
+    }
+
+    /// drops every snapshot newer than `cycle` and returns the one taken at `cycle`
+    ///
+    /// returns None and leaves the history untouched if `cycle` is no longer recorded
+    pub fn rewind_to(&mut self, cycle: usize) -> Option<Snapshot> {
+        let idx = self.snapshots.iter().position(|s| s.cycle == cycle)?;
+        self.snapshots.truncate(idx + 1);
+        self.snapshots.pop_back()
+    }
+
+    /// cycle of the oldest snapshot still recorded
+    pub fn first_cycle(&self) -> Option<usize> {
+        self.snapshots.front().map(|s| s.cycle)
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crab::Crab;
+
+    fn snap(cycle: usize) -> Snapshot {
+        Snapshot {
+            cycle,
+            crab: Crab::new().save(),
+            grid: vec![],
+            code_finished: false,
+        }
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut history = History::new(3);
+        for i in 0..5 {
+            history.push(snap(i));
+        }
+        assert_eq!(history.snapshots.len(), 3);
+        assert_eq!(history.first_cycle(), Some(2));
+        assert_eq!(history.pop().unwrap().cycle, 4);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut history = History::new(10);
+        for i in 0..5 {
+            history.push(snap(i));
+        }
+        assert!(history.rewind_to(7).is_none());
+        assert_eq!(history.snapshots.len(), 5);
+        assert_eq!(history.rewind_to(2).unwrap().cycle, 2);
+        assert_eq!(history.snapshots.len(), 2);
+        assert_eq!(history.pop().unwrap().cycle, 1);
+    }
+}
@@ -0,0 +1,205 @@
+//! the instruction set, read by the parser, the formatter, autocompletion, the in-game reference and `crabs isa`
+
+use crate::crab::Register;
+use std::fmt;
+
+/// what an instruction expects in one operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// a number or a register
+    Value,
+    Register,
+    Label,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Value => write!(f, "<int|reg>"),
+            Operand::Register => write!(f, "<reg>"),
+            Operand::Label => write!(f, "<label>"),
+        }
+    }
+}
+
+/// an operand as written in a program
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Num(i32),
+    Reg(Register),
+    Label(String),
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::Num(num) => write!(f, "{}", num),
+            Arg::Reg(reg) => write!(f, "{:?}", reg),
+            Arg::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+pub struct Instruction {
+    pub mnemonic: &'static str,
+    pub operands: &'static [Operand],
+    /// cycles the instruction takes to run
+    pub cycles: usize,
+    pub description: &'static str,
+    /// one or more lines of code using it
+    pub example: &'static str,
+}
+
+impl Instruction {
+    /// the instruction with its operands, such as `ADD <int|reg> <reg>`
+    pub fn signature(&self) -> String {
+        self.operands.iter().fold(self.mnemonic.to_owned(), |sig, op| format!("{} {}", sig, op))
+    }
+}
+
+use self::Operand::*;
+
+pub const INSTRUCTIONS: [Instruction; 15] = [
+    Instruction {
+        mnemonic: "NOP",
+        operands: &[],
+        cycles: 1,
+        description: "Does nothing.",
+        example: "NOP",
+    },
+    Instruction {
+        mnemonic: "MOV",
+        operands: &[Value, Register],
+        cycles: 1,
+        description: "Copies a number or a register into a register.",
+        example: "MOV 1 A\nMOV H M",
+    },
+    Instruction {
+        mnemonic: "ADD",
+        operands: &[Value, Register],
+        cycles: 1,
+        description: "Adds a number or a register to a register.",
+        example: "ADD 1 A\nADD A A",
+    },
+    Instruction {
+        mnemonic: "SUB",
+        operands: &[Value, Register],
+        cycles: 1,
+        description: "Subtracts a number or a register from a register.",
+        example: "SUB 1 M\nSUB V A",
+    },
+    Instruction {
+        mnemonic: "NEG",
+        operands: &[Register],
+        cycles: 1,
+        description: "Negates a register.",
+        example: "NEG M",
+    },
+    Instruction {
+        mnemonic: "JMP",
+        operands: &[Label],
+        cycles: 1,
+        description: "Jumps to a label.",
+        example: "JMP LOOP",
+    },
+    Instruction {
+        mnemonic: "JEZ",
+        operands: &[Label],
+        cycles: 1,
+        description: "Jumps to a label if A is 0.",
+        example: "JEZ DONE",
+    },
+    Instruction {
+        mnemonic: "JNZ",
+        operands: &[Label],
+        cycles: 1,
+        description: "Jumps to a label if A is not 0.",
+        example: "JNZ LOOP",
+    },
+    Instruction {
+        mnemonic: "JGZ",
+        operands: &[Label],
+        cycles: 1,
+        description: "Jumps to a label if A is greater than 0.",
+        example: "JGZ RIGHT",
+    },
+    Instruction {
+        mnemonic: "JLZ",
+        operands: &[Label],
+        cycles: 1,
+        description: "Jumps to a label if A is less than 0.",
+        example: "JLZ LEFT",
+    },
+    Instruction {
+        mnemonic: "JRO",
+        operands: &[Value],
+        cycles: 1,
+        description: "Jumps by a number of lines or the value of a register, JRO -1 goes to the line before and JRO 0 stays.",
+        example: "JRO -1\nJRO A",
+    },
+    Instruction {
+        mnemonic: "RCW",
+        operands: &[],
+        cycles: 1,
+        description: "Turns the crab clockwise.",
+        example: "RCW",
+    },
+    Instruction {
+        mnemonic: "RCC",
+        operands: &[],
+        cycles: 1,
+        description: "Turns the crab counterclockwise.",
+        example: "RCC",
+    },
+    Instruction {
+        mnemonic: "IN",
+        operands: &[Register],
+        cycles: 1,
+        description: "Reads the next value of the level's input into a register. Reading past the end of the input is an error.",
+        example: "IN A",
+    },
+    Instruction {
+        mnemonic: "OUT",
+        operands: &[Value],
+        cycles: 1,
+        description: "Writes a number or a register to the output. On levels with an expected output every value has to match, a wrong or extra value is an error.",
+        example: "OUT A\nOUT -1",
+    },
+];
+
+pub fn find(mnemonic: &str) -> Option<&'static Instruction> {
+    INSTRUCTIONS.iter().find(|i| i.mnemonic == mnemonic)
+}
+
+/// every instruction in markdown, as in the README
+pub fn reference() -> String {
+    let mut doc = String::new();
+    for instr in INSTRUCTIONS.iter() {
+        let cycles = if instr.cycles == 1 { "1 cycle".to_owned() } else { format!("{} cycles", instr.cycles) };
+        doc.push_str(&format!("### `{}`\n\n{} Takes {}.\n\n", instr.signature(), instr.description, cycles));
+        for line in instr.example.lines() {
+            doc.push_str(&format!("    {}\n", line));
+        }
+        doc.push('\n');
+    }
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crab::OpCode;
+
+    #[test]
+    fn test_roundtrip() {
+        for instr in INSTRUCTIONS.iter() {
+            for line in instr.example.lines() {
+                let op: OpCode = line.parse().unwrap();
+                assert_eq!(op.to_string(), line);
+                assert_eq!(op.to_string().parse::<OpCode>().unwrap(), op);
+            }
+        }
+        assert_eq!(find("ADD").unwrap().signature(), "ADD <int|reg> <reg>");
+        assert!(include_str!("../README.md").contains(&reference()), "README is out of date, run crabs isa");
+    }
+}
@@ -0,0 +1,329 @@
+//! what the keys do, with defaults that a keys file can change
+//!
+//! the file has a line `<action>: <chord>, <chord>` for every action to rebind, such as
+//! `step: Ctrl+Return, F8`, and `<action>:` alone leaves an action without keys
+
+use crate::editor::Edit;
+use std::fmt;
+use std::str::FromStr;
+
+/// file in the working directory that changes the default bindings
+pub const KEYS_FILE: &str = "keys.txt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Top,
+    Bottom,
+    Backspace,
+    Delete,
+    Newline,
+    SelectAll,
+    /// stops instead while a program runs
+    Copy,
+    Cut,
+    Paste,
+    Undo,
+    Redo,
+    Complete,
+    Step,
+    StepBack,
+    Play,
+    Stop,
+    /// closes the prompt or the reference if one is open, else drops the focus, otherwise stops
+    Cancel,
+    RunToCursor,
+    FastForward,
+    CycleSpeed,
+    ToggleBreakpoint,
+    EditCondition,
+    EditWatch,
+    Solution,
+    TraceJsonl,
+    TraceCsv,
+    SaveReplay,
+    LoadReplay,
+    Reference,
+    Keys,
+    /// moves the keyboard focus to the next button, newline then clicks it
+    FocusNext,
+    FocusPrev,
+}
+
+use self::Action::*;
+
+/// every action with its name in the keys file, what it does and its default keys
+const ACTIONS: [(Action, &str, &str, &[&str]); 38] = [
+    (Left, "left", "cursor left", &["Left"]),
+    (Right, "right", "cursor right", &["Right"]),
+    (Up, "up", "cursor up", &["Up"]),
+    (Down, "down", "cursor down", &["Down"]),
+    (Home, "home", "start of line", &["Home"]),
+    (End, "end", "end of line", &["End"]),
+    (Top, "top", "start of program", &["Ctrl+Home"]),
+    (Bottom, "bottom", "end of program", &["Ctrl+End"]),
+    (Backspace, "backspace", "delete before cursor", &["Back"]),
+    (Delete, "delete", "delete after cursor", &["Delete"]),
+    (Newline, "newline", "new line", &["Return"]),
+    (SelectAll, "select-all", "select all", &["Ctrl+A"]),
+    (Copy, "copy", "copy, stop while running", &["Ctrl+C"]),
+    (Cut, "cut", "cut", &["Ctrl+X"]),
+    (Paste, "paste", "paste", &["Ctrl+V"]),
+    (Undo, "undo", "undo", &["Ctrl+Z"]),
+    (Redo, "redo", "redo", &["Ctrl+Y", "Ctrl+Shift+Z"]),
+    (Complete, "complete", "accept suggestion", &["Tab"]),
+    (Step, "step", "step", &["Ctrl+Return"]),
+    (StepBack, "step-back", "step back", &["Ctrl+Back"]),
+    (Play, "play", "play or pause", &["F5"]),
+    (Stop, "stop", "stop", &["Shift+F5"]),
+    (Cancel, "cancel", "close or stop", &["Escape"]),
+    (RunToCursor, "run-to-cursor", "run to cursor", &["F4"]),
+    (FastForward, "fast-forward", "run to end", &["F11"]),
+    (CycleSpeed, "speed", "change speed", &["F6"]),
+    (ToggleBreakpoint, "breakpoint", "toggle breakpoint", &["F9"]),
+    (EditCondition, "condition", "breakpoint condition", &["F8"]),
+    (EditWatch, "watch", "watch register", &["F7"]),
+    (Solution, "solution", "show a solution", &["F1"]),
+    (TraceJsonl, "trace-jsonl", "trace to jsonl", &["F2"]),
+    (TraceCsv, "trace-csv", "trace to csv", &["F3"]),
+    (SaveReplay, "save-replay", "save replay", &["F10"]),
+    (LoadReplay, "load-replay", "load replay", &["F12"]),
+    (Reference, "reference", "instruction reference", &["Ctrl+R"]),
+    (Keys, "keys", "this list", &["Ctrl+K"]),
+    (FocusNext, "focus-next", "focus next button", &["Ctrl+Tab"]),
+    (FocusPrev, "focus-prev", "focus previous button", &["Ctrl+Shift+Tab"]),
+];
+
+/// keys that are not letters, digits or function keys, named as in quicksilver's `Key`
+const NAMED_KEYS: [&str; 32] = [
+    "Escape", "Insert", "Home", "Delete", "End", "PageDown", "PageUp", "Left", "Up", "Right", "Down",
+    "Back", "Return", "Space", "Tab", "Pause", "Minus", "Equals", "Comma", "Period", "Slash", "Backslash",
+    "Semicolon", "Apostrophe", "Grave", "LBracket", "RBracket", "Add", "Subtract", "Multiply", "Divide",
+    "NumpadEnter",
+];
+
+/// the named keys that also type a character
+const PRINTABLE_KEYS: [&str; 16] = [
+    "Space", "Minus", "Equals", "Comma", "Period", "Slash", "Backslash", "Semicolon", "Apostrophe", "Grave",
+    "LBracket", "RBracket", "Add", "Subtract", "Multiply", "Divide",
+];
+
+impl Action {
+    /// the editor command the action runs, if it is one
+    pub fn edit(self) -> Option<Edit> {
+        Some(match self {
+            Left => Edit::Left,
+            Right => Edit::Right,
+            Up => Edit::Up,
+            Down => Edit::Down,
+            Home => Edit::Home,
+            End => Edit::End,
+            Top => Edit::Top,
+            Bottom => Edit::Bottom,
+            Backspace => Edit::Backspace,
+            Delete => Edit::Delete,
+            SelectAll => Edit::SelectAll,
+            Cut => Edit::Cut,
+            Paste => Edit::Paste,
+            Undo => Edit::Undo,
+            Redo => Edit::Redo,
+            _ => return None,
+        })
+    }
+
+    /// moves the cursor, extending the selection while shift is held
+    fn is_move(self) -> bool {
+        matches!(self, Left | Right | Up | Down | Home | End | Top | Bottom)
+    }
+
+    fn entry(self) -> &'static (Action, &'static str, &'static str, &'static [&'static str]) {
+        ACTIONS.iter().find(|entry| entry.0 == self).unwrap()
+    }
+
+    pub fn description(self) -> &'static str {
+        self.entry().2
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.entry().1)
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ACTIONS.iter().find(|entry| entry.1 == s).map(|entry| entry.0).ok_or_else(|| format!("unknown action {}", s))
+    }
+}
+
+/// a key together with the modifiers held down
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+    /// name of quicksilver's `Key`, such as `Return` or `F5`
+    pub key: String,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Chord {
+    pub fn new(key: &str, ctrl: bool, shift: bool, alt: bool) -> Chord {
+        Chord { key: key.to_owned(), ctrl, shift, alt }
+    }
+
+    /// whether pressing the chord also types into the editor, which only Ctrl stops
+    fn types(&self) -> bool {
+        let digit = matches!(self.key.strip_prefix("Key").map(str::as_bytes), Some([b'0'..=b'9']));
+        let letter = matches!(self.key.as_bytes(), [b'A'..=b'Z']);
+        !self.ctrl && (digit || letter || PRINTABLE_KEYS.contains(&self.key.as_str()))
+    }
+}
+
+fn is_key(name: &str) -> bool {
+    let fn_key = matches!(name.strip_prefix('F').map(str::parse::<u32>), Some(Ok(1..=24)));
+    let digit = matches!(name.strip_prefix("Key").map(str::as_bytes), Some([b'0'..=b'9']));
+    let letter = matches!(name.as_bytes(), [b'A'..=b'Z']);
+    fn_key || digit || letter || NAMED_KEYS.contains(&name)
+}
+
+impl FromStr for Chord {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap();
+        if !is_key(key) {
+            return Err(format!("unknown key {}", key));
+        }
+        let mut chord = Chord::new(key, false, false, false);
+        for modifier in parts {
+            let held = match modifier.to_lowercase().as_str() {
+                "ctrl" => &mut chord.ctrl,
+                "shift" => &mut chord.shift,
+                "alt" => &mut chord.alt,
+                _ => return Err(format!("unknown modifier {}", modifier)),
+            };
+            *held = true;
+        }
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl { write!(f, "Ctrl+")? }
+        if self.alt { write!(f, "Alt+")? }
+        if self.shift { write!(f, "Shift+")? }
+        write!(f, "{}", self.key)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Chord, Action)>,
+}
+
+impl Keymap {
+    /// the default bindings
+    pub fn new() -> Keymap {
+        let bindings = ACTIONS.iter()
+            .flat_map(|(action, _, _, keys)| keys.iter().map(move |key| (key.parse().unwrap(), *action)))
+            .collect();
+        Keymap { bindings }
+    }
+
+    /// the defaults changed by the lines of a keys file
+    ///
+    /// keys the file binds are taken from the actions they did default to
+    pub fn parse(text: &str) -> Result<Keymap, String> {
+        let mut keymap = Keymap::new();
+        let mut bound: Vec<(Chord, Action)> = vec![];
+        let mut rebound = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let err = |e: String| format!("line {}: {}", i + 1, e);
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() { continue }
+            let mut split = line.splitn(2, ':');
+            let action: Action = split.next().unwrap().trim().parse().map_err(err)?;
+            let chords = split.next().ok_or_else(|| err(format!("expected {}: <keys>", action)))?;
+            rebound.push(action);
+            for chord in chords.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                let chord: Chord = chord.parse().map_err(err)?;
+                if chord.types() {
+                    return Err(err(format!("{} also types a character, hold Ctrl with it", chord)));
+                }
+                if let Some((_, other)) = bound.iter().find(|(c, _)| *c == chord) {
+                    return Err(err(format!("{} is already bound to {}", chord, other)));
+                }
+                bound.push((chord, action));
+            }
+        }
+        keymap.bindings.retain(|(chord, action)| !rebound.contains(action) && !bound.iter().any(|(c, _)| c == chord));
+        keymap.bindings.extend(bound);
+        Ok(keymap)
+    }
+
+    /// the defaults changed by `KEYS_FILE` if there is one
+    pub fn load() -> Result<Keymap, String> {
+        match std::fs::read_to_string(KEYS_FILE) {
+            Ok(text) => Keymap::parse(&text).map_err(|e| format!("{}: {}", KEYS_FILE, e)),
+            Err(_) => Ok(Keymap::new()),
+        }
+    }
+
+    /// the action for a chord and whether it extends the selection
+    ///
+    /// shift is ignored for chords not bound with it, moves then select
+    pub fn lookup(&self, chord: &Chord) -> Option<(Action, bool)> {
+        let find = |chord: &Chord| self.bindings.iter().find(|(c, _)| c == chord).map(|(_, action)| *action);
+        if let Some(action) = find(chord) {
+            return Some((action, false));
+        }
+        if !chord.shift { return None }
+        let action = find(&Chord { shift: false, ..chord.clone() })?;
+        Some((action, action.is_move()))
+    }
+
+    /// the keys of every action that has some, as `description: keys` lines
+    pub fn help(&self) -> Vec<String> {
+        ACTIONS.iter().filter_map(|(action, ..)| {
+            let keys: Vec<_> = self.bindings.iter().filter(|(_, a)| a == action).map(|(c, _)| c.to_string()).collect();
+            if keys.is_empty() { return None }
+            Some(format!("{}: {}", action.description(), keys.join(", ")))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keymap() {
+        let keymap = Keymap::new();
+        let chord = |s: &str| s.parse::<Chord>().unwrap();
+        assert_eq!(keymap.lookup(&chord("Ctrl+Return")), Some((Step, false)));
+        assert_eq!(keymap.lookup(&chord("Shift+Left")), Some((Left, true)));
+        assert_eq!(keymap.lookup(&chord("Ctrl+Shift+End")), Some((Bottom, true)));
+        assert_eq!(keymap.lookup(&chord("Ctrl+Shift+Z")), Some((Redo, false)));
+        assert_eq!(keymap.lookup(&chord("Shift+Back")), Some((Backspace, false)));
+        assert_eq!(keymap.lookup(&chord("Q")), None);
+        assert_eq!(chord("shift+ctrl+F5").to_string(), "Ctrl+Shift+F5");
+
+        let keymap = Keymap::parse("# mine\nstep: F8, Ctrl+Return\nplay:\n").unwrap();
+        assert_eq!(keymap.lookup(&chord("F8")), Some((Step, false)));
+        assert_eq!(keymap.lookup(&chord("F5")), None);
+        assert!(keymap.help().contains(&"step: F8, Ctrl+Return".to_owned()));
+
+        assert_eq!(Keymap::parse("jump: F1").unwrap_err(), "line 1: unknown action jump");
+        assert_eq!(Keymap::parse("\nstep: Ctrl+Enter").unwrap_err(), "line 2: unknown key Enter");
+        assert_eq!(Keymap::parse("step: F8\nplay: F8").unwrap_err(), "line 2: F8 is already bound to step");
+        assert_eq!(Keymap::parse("step: Alt+S").unwrap_err(), "line 1: Alt+S also types a character, hold Ctrl with it");
+    }
+}
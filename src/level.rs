@@ -0,0 +1,198 @@
+use crate::sim::CYCLE_LIMIT;
+use crate::generator::{self, Params};
+use std::sync::OnceLock;
+
+pub const GRID_W: usize = 8;
+pub const GRID_H: usize = 6;
+
+/// `true` marks a tile the crab still has to collect
+pub type Grid = Vec<Vec<bool>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    /// test variants, a program has to clear every one of them in turn
+    pub grids: Vec<Grid>,
+    /// cycles a program may take before it times out
+    pub cycle_limit: usize,
+    /// values `IN` reads, the same for every variant
+    pub input: Vec<i32>,
+    /// values the program has to write with `OUT`
+    pub output: Vec<i32>,
+    /// lines a program may have, None for no limit
+    pub max_lines: Option<usize>,
+    /// characters a line may have, None for no limit
+    pub max_len: Option<usize>,
+}
+
+/// the built in levels, parsed once since `seeds:` lines run the generator
+pub fn levels() -> Vec<Level> {
+    static LEVELS: OnceLock<Vec<Level>> = OnceLock::new();
+    LEVELS.get_or_init(|| vec![
+        parse(include_str!("levels/tutorial.txt")),
+        parse(include_str!("levels/tutorial2.txt")),
+        parse(include_str!("levels/tutorial3.txt")),
+        parse(include_str!("levels/tutorial4.txt")),
+        parse(include_str!("levels/2.txt")),
+        parse(include_str!("levels/1.txt")),
+        parse(include_str!("levels/test.txt")),
+        parse(include_str!("levels/impossible.txt")),
+        parse(include_str!("levels/variants.txt")),
+        parse(include_str!("levels/double.txt")),
+    ]).clone()
+}
+
+/// reads a level file, the grid comes first and may be followed by notes
+///
+/// a `cycles: <n>` line after the grid sets the cycle limit. Each `variant:`
+/// line is followed by another grid, and a `seeds: <n>...` line adds a
+/// generated grid for every seed. `in: <n>...` and `out: <n>...` lines set
+/// the input and the expected output, `lines: <n>` and `columns: <n>` limit
+/// the size of programs.
+pub fn parse(file: &str) -> Level {
+    let numbers = |rest: &str| -> Vec<i32> {
+        rest.split_whitespace().filter_map(|n| n.parse().ok()).collect()
+    };
+    let (mut input, mut output) = (vec![], vec![]);
+    let mut cycle_limit = CYCLE_LIMIT;
+    let (mut max_lines, mut max_len) = (None, None);
+    let mut grids = vec![to_grid(file)];
+    let mut seeds = vec![];
+    let lines: Vec<_> = file.lines().collect();
+    let mut i = GRID_H;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        if let Some(n) = line.strip_prefix("cycles:") {
+            cycle_limit = n.trim().parse().unwrap_or(cycle_limit);
+        } else if let Some(n) = line.strip_prefix("lines:") {
+            max_lines = n.trim().parse().ok();
+        } else if let Some(n) = line.strip_prefix("columns:") {
+            max_len = n.trim().parse().ok();
+        } else if line == "variant:" {
+            grids.push(to_grid(&lines[i + 1..].join("\n")));
+            i += GRID_H;
+        } else if let Some(rest) = line.strip_prefix("in:") {
+            input = numbers(rest);
+        } else if let Some(rest) = line.strip_prefix("out:") {
+            output = numbers(rest);
+        } else if let Some(rest) = line.strip_prefix("seeds:") {
+            seeds.extend(rest.split_whitespace().filter_map(|n| n.parse::<u64>().ok()));
+        }
+        i += 1;
+    }
+    // seeds the generator cannot make a level from are left out
+    grids.extend(seeds.into_iter().filter_map(|seed| generator::generate(seed, &Params::default()).ok()).map(|l| l.grids[0].clone()));
+    Level { grids, cycle_limit, input, output, max_lines, max_len }
+}
+
+/// loads a level by its number starting at 1, or from a level file
+pub fn load(arg: &str) -> Result<Level, String> {
+    if let Ok(n) = arg.parse::<usize>() {
+        let levels = levels();
+        return n.checked_sub(1)
+            .and_then(|i| levels.get(i).cloned())
+            .ok_or(format!("there is no level {}, pick one from 1 to {}", n, levels.len()));
+    }
+    let file = std::fs::read_to_string(arg).map_err(|e| format!("cannot read {}: {}", arg, e))?;
+    Ok(parse(&file))
+}
+
+pub fn to_grid(file: &str) -> Grid {
+    let mut grid = vec![];
+    for line in file.lines().take(GRID_H) {
+        let mut temp = vec![];
+        for ch in line.chars().take(GRID_W) {
+            temp.push(ch=='x');
+        }
+        grid.push(temp);
+    }
+    grid
+}
+
+/// the level in the file format `parse` reads
+pub fn format(level: &Level) -> String {
+    let mut file = String::new();
+    for (i, grid) in level.grids.iter().enumerate() {
+        if i > 0 {
+            file.push_str("variant:\n");
+        }
+        for row in grid {
+            file.extend(row.iter().map(|tile| if *tile { 'x' } else { '_' }));
+            file.push('\n');
+        }
+        if i == 0 {
+            file.push_str(&format!("cycles: {}\n", level.cycle_limit));
+            if let Some(n) = level.max_lines {
+                file.push_str(&format!("lines: {}\n", n));
+            }
+            if let Some(n) = level.max_len {
+                file.push_str(&format!("columns: {}\n", n));
+            }
+        }
+    }
+    let numbers = |values: &[i32]| values.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(" ");
+    if !level.input.is_empty() {
+        file.push_str(&format!("in: {}\n", numbers(&level.input)));
+    }
+    if !level.output.is_empty() {
+        file.push_str(&format!("out: {}\n", numbers(&level.output)));
+    }
+    file
+}
+
+/// true once every tile has been collected
+pub fn cleared(grid: &Grid) -> bool {
+    grid.iter().all(|row| row.iter().all(|tile| !tile))
+}
+
+/// the tiles still to collect as `(x, y)`, row by row
+pub fn tiles(grid: &Grid) -> Vec<(i32, i32)> {
+    grid.iter().enumerate()
+        .flat_map(|(y, row)| row.iter().enumerate().filter(|(_, t)| **t).map(move |(x, _)| (x as i32, y as i32)))
+        .collect()
+}
+
+/// FNV-1a hash of the layouts of every variant and the streams, used to tell whether a level changed
+pub fn hash(level: &Level) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut add = |bytes: &mut dyn Iterator<Item = u8>| {
+        for byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    for row in level.grids.iter().flatten() {
+        // rows end with a newline so layouts of different widths differ
+        add(&mut row.iter().map(|tile| if *tile { b'x' } else { b'_' }).chain(Some(b'\n')));
+    }
+    // levels without streams hash the same as before there were any
+    for (key, values) in [("in", &level.input), ("out", &level.output)].iter() {
+        if !values.is_empty() {
+            let line = format!("{}: {:?}\n", key, values);
+            add(&mut line.bytes());
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let level = parse("x_\n__\n\n\n\n\ncycles: 40\nLOOP:\n");
+        assert_eq!(level.grids, vec![vec![vec![true, false], vec![false, false], vec![], vec![], vec![], vec![]]]);
+        assert_eq!(level.cycle_limit, 40);
+        assert_eq!(parse("x_\n").cycle_limit, CYCLE_LIMIT);
+        assert_eq!(parse("x_\n\n\n\n\n\nlines: 12\n").max_lines, Some(12));
+        assert_eq!(parse("x_\n").max_len, None);
+        for level in levels() {
+            assert_eq!(parse(&format(&level)), level);
+        }
+
+        let level = parse("x_\n\n\n\n\n\nvariant:\n_x\n\n\n\n\n\nseeds: 3\n");
+        assert_eq!(level.grids.len(), 3);
+        assert_eq!(level.grids[1][0], vec![false, true]);
+        assert_eq!(level.grids[2], generator::generate(3, &Params::default()).unwrap().grids[0]);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::crab::{OpCode, Register};
+use crate::flow::successors;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintKind {
+    Unreachable,
+    UnusedLabel(String),
+    /// a label with the same name earlier in the program, jumps never land here
+    ShadowedLabel(String),
+    UnknownLabel(String),
+    /// `R` can only be changed with `RCW` and `RCC`, the write is ignored
+    WriteToR,
+    /// a loop that changes no register while the motor is never switched on
+    Hang,
+    /// execution can continue past the last instruction
+    FallsOffEnd,
+    /// `JRO` with an immediate that always lands outside the program
+    JumpOutOfBounds(i32),
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintKind::Unreachable => write!(f, "unreachable instruction"),
+            LintKind::UnusedLabel(lbl) => write!(f, "label {} is never jumped to", lbl),
+            LintKind::ShadowedLabel(lbl) => write!(f, "label {} is shadowed by an earlier one", lbl),
+            LintKind::UnknownLabel(lbl) => write!(f, "there is no label {}", lbl),
+            LintKind::WriteToR => write!(f, "R is read-only, use RCW or RCC"),
+            LintKind::Hang => write!(f, "loop never ends and the crab never moves"),
+            LintKind::FallsOffEnd => write!(f, "program runs off the end here"),
+            LintKind::JumpOutOfBounds(n) => write!(f, "JRO {} always jumps out of the program", n),
+        }
+    }
+}
+
+/// a warning about a line of the program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    /// index of the instruction, which is also its line in the editor
+    pub line: usize,
+    pub kind: LintKind,
+}
+
+/// register named as the destination operand, if any
+pub fn destination(op: &OpCode) -> Option<Register> {
+    use crate::crab::OpCode::*;
+    match op {
+        MOVI(_, reg) | MOV(_, reg) | ADDI(_, reg) | ADD(_, reg)
+        | SUBI(_, reg) | SUB(_, reg) | NEG(reg) => Some(*reg),
+        _ => None,
+    }
+}
+
+/// register an instruction actually changes, if any
+pub fn writes(op: &OpCode) -> Option<Register> {
+    match op {
+        OpCode::RCW | OpCode::RCC => Some(Register::R),
+        OpCode::IN(reg) if *reg != Register::R => Some(*reg),
+        op => destination(op).filter(|reg| *reg != Register::R),
+    }
+}
+
+/// every instruction reachable from `start`, possibly including `code.len()`
+fn reachable_from(code: &[OpCode], start: &[usize]) -> BTreeSet<usize> {
+    let mut seen = BTreeSet::new();
+    let mut stack = start.to_vec();
+    while let Some(ip) = stack.pop() {
+        if !seen.insert(ip) || ip >= code.len() {
+            continue;
+        }
+        stack.extend(successors(code, ip));
+    }
+    seen
+}
+
+pub fn lint(code: &[OpCode]) -> Vec<Lint> {
+    use crate::crab::OpCode::*;
+    let mut ret = vec![];
+    if code.is_empty() {
+        return ret;
+    }
+    let reachable = reachable_from(code, &[0]);
+
+    // labels
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    for (ip, op) in code.iter().enumerate() {
+        if let LABEL(lbl) = op {
+            if labels.contains_key(lbl.as_str()) {
+                ret.push(Lint { line: ip, kind: LintKind::ShadowedLabel(lbl.clone()) });
+            } else {
+                labels.insert(lbl, ip);
+            }
+        }
+    }
+    let mut used = BTreeSet::new();
+    for (ip, op) in code.iter().enumerate() {
+        match op {
+            JMP(lbl) | JEZ(lbl) | JNZ(lbl) | JGZ(lbl) | JLZ(lbl) => {
+                match labels.get(lbl.as_str()) {
+                    Some(_) => { used.insert(lbl.as_str()); }
+                    None => ret.push(Lint { line: ip, kind: LintKind::UnknownLabel(lbl.clone()) }),
+                }
+            }
+            _ => (),
+        }
+    }
+    for (lbl, ip) in &labels {
+        if !used.contains(lbl) {
+            ret.push(Lint { line: *ip, kind: LintKind::UnusedLabel(lbl.to_string()) });
+        }
+    }
+
+    let motor_used = code.iter().any(|op| writes(op) == Some(Register::M));
+    let mut hangs = BTreeSet::new();
+    for (ip, op) in code.iter().enumerate() {
+        match op {
+            JROI(n) if successors(code, ip).is_empty() => {
+                ret.push(Lint { line: ip, kind: LintKind::JumpOutOfBounds(*n) });
+            }
+            _ if destination(op) == Some(Register::R) => {
+                ret.push(Lint { line: ip, kind: LintKind::WriteToR });
+            }
+            _ => (),
+        }
+        if !reachable.contains(&ip) {
+            match op {
+                LABEL(_) | COMMENT(_) => (),
+                _ => ret.push(Lint { line: ip, kind: LintKind::Unreachable }),
+            }
+            continue;
+        }
+        if successors(code, ip).contains(&code.len()) {
+            ret.push(Lint { line: ip, kind: LintKind::FallsOffEnd });
+        }
+        // a loop that can never be left and changes nothing
+        let closure = reachable_from(code, &successors(code, ip));
+        if !motor_used
+            && closure.contains(&ip)
+            && !closure.contains(&code.len())
+            && closure.iter().all(|i| writes(&code[*i]).is_none())
+        {
+            let head = *closure.iter().next().unwrap();
+            if hangs.insert(head) {
+                ret.push(Lint { line: head, kind: LintKind::Hang });
+            }
+        }
+    }
+
+    ret.sort_by_key(|l| l.line);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crab::Crab;
+
+    fn kinds(code: &str) -> Vec<(usize, LintKind)> {
+        let mut crab = Crab::new();
+        crab.load_code(code).unwrap();
+        lint(&crab.code).into_iter().map(|l| (l.line, l.kind)).collect()
+    }
+
+    #[test]
+    fn test_clean() {
+        assert_eq!(kinds("MOV -1 M\nL:\nJMP L"), vec![]);
+    }
+
+    #[test]
+    fn test_lints() {
+        let code = "L:\nMOV 1 R\nJMP L\nNOP\nL:\nUNUSED:\nJRO -9\nJEZ NOWHERE";
+        assert_eq!(kinds(code), vec![
+            (0, LintKind::Hang),
+            (1, LintKind::WriteToR),
+            (3, LintKind::Unreachable),
+            (4, LintKind::ShadowedLabel("L".to_owned())),
+            (5, LintKind::UnusedLabel("UNUSED".to_owned())),
+            (6, LintKind::JumpOutOfBounds(-9)),
+            (6, LintKind::Unreachable),
+            (7, LintKind::UnknownLabel("NOWHERE".to_owned())),
+            (7, LintKind::Unreachable),
+        ]);
+        assert_eq!(kinds("MOV 1 M\nNOP"), vec![(1, LintKind::FallsOffEnd)]);
+    }
+}
@@ -5,7 +5,27 @@ mod prelude;
 mod anim;
 mod game;
 mod crab;
+mod editor;
+mod syntax;
+mod isa;
+mod help;
+mod keymap;
+mod widget;
+mod complete;
 mod sprites;
+mod history;
+mod debugger;
+mod level;
+mod sim;
+mod trace;
+mod replay;
+mod flow;
+mod lint;
+mod bench;
+mod solver;
+mod synth;
+mod generator;
+mod cli;
 
 #[cfg(target_arch="wasm32")]
 const MULT: f32 =  5.;
@@ -13,6 +33,9 @@ const MULT: f32 =  5.;
 const MULT: f32 =  1.;
 
 use crate::prelude::*;
+use crate::trace::TraceFormat;
+use crate::editor::Edit;
+use crate::keymap::{Action, Chord, Keymap};
 
 struct Crabs {
     sprites: Asset<Sprites>,
@@ -20,23 +43,25 @@ struct Crabs {
     mouse_down: bool,
     pos_x: f32,
     pos_y: f32,
-    ctrl: bool,
-    shift: bool,
+    keymap: Keymap,
 }
 
 impl State for Crabs {
 
     fn new() -> Result<Crabs> {
         let sprites = Asset::new(Sprites::new());
-        let game = Game::new();
+        let mut game = Game::new();
+        let keymap = Keymap::load().unwrap_or_else(|e| {
+            game.set_status(e);
+            Keymap::new()
+        });
         Ok(Crabs {
             sprites,
             game,
             mouse_down: false,
             pos_x: 0.,
             pos_y: 0.,
-            ctrl: false,
-            shift: false,
+            keymap,
         })
     }
 
@@ -76,89 +101,61 @@ impl State for Crabs {
 
     fn event(&mut self, event: &Event, window: &mut Window) -> Result<()> {
         match event {
-            Event::Key(Key::Left, ButtonState::Pressed) => {
-                self.game.cursor_left();
-            }
-            Event::Key(Key::Right, ButtonState::Pressed) => {
-                self.game.cursor_right();
-            }
-
-
-            Event::Typed(c) => { self.game.char(char::to_ascii_uppercase(c)); }
-            Event::Key(Key::C, ButtonState::Pressed) => {
-                if self.ctrl {
-                    self.game.stop();
-                    return Ok(());
+            // control characters and the letters of shortcuts are handled as keys
+            Event::Typed(c) => {
+                if !held(window, Key::LControl, Key::RControl) && !c.is_control() {
+                    self.game.char(char::to_ascii_uppercase(c));
                 }
             }
-            Event::Key(Key::Return, ButtonState::Pressed) => {
-                if self.ctrl {
-                    self.game.step(&mut self.sprites);
-                    return Ok(());
+            Event::Key(key, ButtonState::Pressed) => {
+                let chord = Chord::new(
+                    &format!("{:?}", key),
+                    held(window, Key::LControl, Key::RControl),
+                    held(window, Key::LShift, Key::RShift),
+                    held(window, Key::LAlt, Key::RAlt),
+                );
+                if let Some((action, select)) = self.keymap.lookup(&chord) {
+                    self.perform(action, select);
                 }
-                self.game.char('\n')
-            },
-            Event::Key(Key::Escape, ButtonState::Pressed) => { self.game.stop() },
-            Event::Key(Key::Space, ButtonState::Pressed) => { self.game.char(' ') },
-            Event::Key(Key::Back, ButtonState::Pressed) => { self.game.char('\0') },
-            Event::Key(Key::Colon, ButtonState::Pressed) => { self.game.char(':') },
-            Event::Key(Key::Semicolon, ButtonState::Pressed) => {
-                if self.shift { self.game.char(':') }
-            },
-            Event::Key(Key::Minus, ButtonState::Pressed) | Event::Key(Key::Subtract, ButtonState::Pressed) => { self.game.char('-') },
-            Event::Key(Key::LShift, ButtonState::Pressed) | Event::Key(Key::RShift, ButtonState::Pressed) => {
-                self.shift = true;
-            },
-            Event::Key(Key::LControl, ButtonState::Pressed) | Event::Key(Key::RControl, ButtonState::Pressed) => {
-                self.ctrl = true;
-            },
-            Event::Key(k, ButtonState::Pressed) => { dbg!(k); },
+            }
 
             Event::MouseButton( MouseButton::Left, ButtonState::Pressed) => {
-                dbg!(&window.mouse().pos());
-                // js!(
-                //     // format!("{:#?}", &window.mouse().pos())
-                //     console.log(1);
-                // );
                 let Vector {x, y} = window.mouse().pos();
 
-
-                macro_rules! click_sound {
-                    () => {
-                        self.sprites.execute(|i| {
-                            i.get_sound("click").unwrap().play()?;
-                            Ok(())
-                        })?;
+                if !self.game.mouse_down(x / MULT, y / MULT) {
+                    if let Some(cycle) = self.game.timeline_cycle(x / MULT, y / MULT) {
+                        self.game.goto_cycle(cycle, &mut self.sprites);
+                    }
+                    if !self.game.click_gutter(x / MULT, y / MULT) {
+                        self.game.click_editor(x / MULT, y / MULT, held(window, Key::LShift, Key::RShift));
                     }
-                }
-
-                if x> 21.502869*MULT && x< 30.15857*MULT && y> 8.043931*MULT && y< 18.273455*MULT {
-                    click_sound!();
-                    // play
-                    self.game.play();
-                }
-                if x > 46.226604*MULT && x < 58.34049*MULT && y > 7.6740127*MULT && y < 19.53377*MULT {
-                    click_sound!();
-                    self.game.step(&mut self.sprites);
-                }
-                if x > 74.01056*MULT && x < 83.92191*MULT && y > 7.001367*MULT && y < 18.945835*MULT {
-                    click_sound!();
-                    self.game.stop();
                 }
 
                 self.mouse_down = true;
             }
-            Event::Key(Key::LControl, ButtonState::Released) | Event::Key(Key::RControl, ButtonState::Released) => {
-                self.ctrl = false;
-            }
             Event::MouseButton( MouseButton::Left, ButtonState::Released) => {
+                let Vector {x, y} = window.mouse().pos();
+                if self.game.mouse_up(x / MULT, y / MULT, &mut self.sprites) {
+                    self.sprites.execute(|i| {
+                        i.get_sound("click").unwrap().play()?;
+                        Ok(())
+                    })?;
+                }
                 self.mouse_down = false;
             }
 
+            Event::MouseWheel(v) => { self.game.scroll(v.y.signum() as i32) }
             Event::MouseMoved(v) => {
+                self.game.hover(v.x / MULT, v.y / MULT);
                 if self.mouse_down {
                     self.pos_x = v.x;
                     self.pos_y = v.y;
+                    // select text or scrub the timeline while dragging
+                    if !self.game.drag(v.x / MULT, v.y / MULT) {
+                        if let Some(cycle) = self.game.timeline_cycle(v.x / MULT, v.y / MULT) {
+                            self.game.goto_cycle(cycle, &mut self.sprites);
+                        }
+                    }
                 }
             }
             _ => { }
@@ -168,7 +165,64 @@ impl State for Crabs {
 
 }
 
+/// either of a pair of modifier keys is down
+fn held(window: &Window, left: Key, right: Key) -> bool {
+    window.keyboard()[left].is_down() || window.keyboard()[right].is_down()
+}
+
 impl Crabs {
+    fn perform(&mut self, action: Action, select: bool) {
+        if let Some(edit) = action.edit() {
+            self.game.edit(edit, select);
+            return;
+        }
+        match action {
+            Action::Newline => {
+                if !self.game.activate(&mut self.sprites) {
+                    self.game.char('\n');
+                }
+            }
+            Action::Copy => {
+                if self.game.is_debugging() {
+                    self.game.stop();
+                } else {
+                    self.game.edit(Edit::Copy, false);
+                }
+            }
+            Action::Complete => self.game.accept_completion(),
+            Action::Step => self.game.step(&mut self.sprites),
+            Action::StepBack => self.game.step_back(),
+            Action::Play => self.game.play(),
+            Action::Stop => self.game.stop(),
+            Action::Cancel => {
+                if self.game.prompt_open() {
+                    self.game.close_prompt();
+                } else if self.game.overlay_open() {
+                    self.game.close_overlay();
+                } else if !self.game.clear_focus() {
+                    self.game.stop();
+                }
+            }
+            Action::RunToCursor => self.game.run_to_cursor(&mut self.sprites),
+            Action::FastForward => self.game.fast_forward(&mut self.sprites),
+            Action::CycleSpeed => self.game.cycle_speed(),
+            Action::ToggleBreakpoint => self.game.toggle_breakpoint(),
+            Action::EditCondition => self.game.edit_condition(),
+            Action::EditWatch => self.game.edit_watch(),
+            Action::Solution => self.game.show_solution(),
+            Action::TraceJsonl => self.game.toggle_trace(TraceFormat::Jsonl),
+            Action::TraceCsv => self.game.toggle_trace(TraceFormat::Csv),
+            Action::SaveReplay => self.game.save_replay(),
+            Action::LoadReplay => self.game.load_replay(),
+            Action::Reference => self.game.toggle_reference(),
+            Action::Keys => self.game.toggle_key_help(self.keymap.help()),
+            Action::FocusNext => self.game.focus_next(false),
+            Action::FocusPrev => self.game.focus_next(true),
+            // the rest are edits
+            _ => {}
+        }
+    }
+
     fn draw_ui(&mut self, window: &mut Window) -> Result<()> {
         self.sprites.execute(|spr| {
             let anim = spr.get_anim("bg").unwrap();
@@ -198,6 +252,10 @@ impl Crabs {
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
     run::<Crabs>("Crabs", Vector::new(WIDTH, HEIGHT), Settings {
         resize: quicksilver::graphics::ResizeStrategy::Fit,
         fullscreen: false,
@@ -0,0 +1,221 @@
+use crate::crab::{RuntimeError, ISA_VERSION};
+use crate::level;
+use crate::sim::{self, Outcome};
+use std::fmt;
+use std::str::FromStr;
+
+const HEADER: &str = "CRABS REPLAY 2";
+/// separates the header fields from the program source
+const SEPARATOR: &str = "---";
+
+/// a program together with everything needed to run it again exactly
+///
+/// stored as a small text file, see `Display` for the layout
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /// level number or path, as accepted by `level::load`
+    pub level: String,
+    pub level_hash: u64,
+    pub isa: u32,
+    /// seed for the random number generator, none of the levels use one yet
+    pub seed: Option<u64>,
+    pub program: String,
+    /// how the run on each variant ended
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Replay {
+    /// runs `program` on every variant of `level` and records how each run ended
+    pub fn record(level: &str, program: &str) -> Result<Replay, String> {
+        let level_def = level::load(level)?;
+        let outcomes = sim::run_level(&level_def, program, level_def.cycle_limit)
+            .map_err(|line| format!("line {}: not a valid instruction", line + 1))?;
+        Ok(Replay {
+            level: level.to_owned(),
+            level_hash: level::hash(&level_def),
+            isa: ISA_VERSION,
+            seed: None,
+            program: program.to_owned(),
+            outcomes,
+        })
+    }
+
+    /// runs the replay again and checks that it ends the same way it was recorded
+    pub fn verify(&self) -> Result<(), String> {
+        if self.isa != ISA_VERSION {
+            return Err(format!("recorded with ISA version {}, this build has {}", self.isa, ISA_VERSION));
+        }
+        let level_def = level::load(&self.level)?;
+        if level::hash(&level_def) != self.level_hash {
+            return Err(format!("level {} changed since the replay was recorded", self.level));
+        }
+        let rerun = Replay::record(&self.level, &self.program)?;
+        if rerun.outcomes.len() != self.outcomes.len() {
+            return Err(format!("recorded {} variants but the level has {}", self.outcomes.len(), rerun.outcomes.len()));
+        }
+        for (i, (recorded, got)) in self.outcomes.iter().zip(&rerun.outcomes).enumerate() {
+            if recorded != got {
+                return Err(format!("variant {}: recorded \"{}\" but got \"{}\"", i + 1, recorded, got));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "level: {}", self.level)?;
+        writeln!(f, "level_hash: {:016x}", self.level_hash)?;
+        writeln!(f, "isa: {}", self.isa)?;
+        match self.seed {
+            Some(seed) => writeln!(f, "seed: {}", seed)?,
+            None => writeln!(f, "seed: none")?,
+        }
+        for outcome in &self.outcomes {
+            writeln!(f, "outcome: {}", write_outcome(outcome))?;
+        }
+        writeln!(f, "{}", SEPARATOR)?;
+        write!(f, "{}", self.program)
+    }
+}
+
+impl FromStr for Replay {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some(HEADER) {
+            return Err("not a replay file".to_owned());
+        }
+        let (mut level, mut level_hash, mut isa, mut seed) = (None, None, None, None);
+        let mut outcomes = vec![];
+        for line in &mut lines {
+            if line.trim() == SEPARATOR {
+                break;
+            }
+            let mut kv = line.splitn(2, ':');
+            let key = kv.next().unwrap().trim();
+            let val = kv.next().ok_or(format!("invalid replay line {}", line))?.trim();
+            match key {
+                "level" => level = Some(val.to_owned()),
+                "level_hash" => level_hash = Some(u64::from_str_radix(val, 16)
+                    .map_err(|_| format!("invalid level hash {}", val))?),
+                "isa" => isa = Some(val.parse().map_err(|_| format!("invalid ISA version {}", val))?),
+                "seed" => seed = Some(match val {
+                    "none" => None,
+                    val => Some(val.parse().map_err(|_| format!("invalid seed {}", val))?),
+                }),
+                "outcome" => outcomes.push(parse_outcome(val)?),
+                _ => return Err(format!("unknown replay field {}", key)),
+            }
+        }
+        if outcomes.is_empty() {
+            return Err("replay has no outcome".to_owned());
+        }
+        let program: Vec<_> = lines.collect();
+        Ok(Replay {
+            level: level.ok_or("replay has no level")?,
+            level_hash: level_hash.ok_or("replay has no level hash")?,
+            isa: isa.ok_or("replay has no ISA version")?,
+            seed: seed.unwrap_or(None),
+            program: program.join("\n"),
+            outcomes,
+        })
+    }
+}
+
+/// an outcome as stored in a replay
+///
+/// errors are written field by field instead of as their message, so rewording a
+/// message does not break old replays
+fn write_outcome(outcome: &Outcome) -> String {
+    let (cycles, error) = match outcome {
+        Outcome::Error(cycles, error) => (cycles, error),
+        outcome => return outcome.to_string(),
+    };
+    let fields = match error {
+        RuntimeError::IpOutOfBounds { ip } => format!("ip-out-of-bounds ip={}", ip),
+        RuntimeError::BadJumpTarget { from, to } => format!("bad-jump from={} to={}", from, to),
+        RuntimeError::UnknownLabel { line, label } => format!("unknown-label line={} label={}", line, label),
+        RuntimeError::InputExhausted { line } => format!("input-exhausted line={}", line),
+        RuntimeError::WrongOutput { line, got, expected } => {
+            let expected = expected.map(|n| n.to_string()).unwrap_or_else(|| "none".to_owned());
+            format!("wrong-output line={} got={} expected={}", line, got, expected)
+        }
+    };
+    format!("error {} {}", cycles, fields)
+}
+
+fn parse_outcome(s: &str) -> Result<Outcome, String> {
+    let words: Vec<_> = s.split_whitespace().collect();
+    let cycles = words.get(1).and_then(|n| n.parse().ok());
+    let outcome = match (words.as_slice(), cycles) {
+        (["cleared", _], Some(cycles)) => Some(Outcome::Cleared(cycles)),
+        (["halted", _], Some(cycles)) => Some(Outcome::Halted(cycles)),
+        (["timeout", _], Some(cycles)) => Some(Outcome::Timeout(cycles)),
+        (["error", _, kind, fields @ ..], Some(cycles)) => parse_error(kind, fields).map(|e| Outcome::Error(cycles, e)),
+        _ => None,
+    };
+    outcome.ok_or(format!("invalid outcome {}", s))
+}
+
+fn parse_error(kind: &str, fields: &[&str]) -> Option<RuntimeError> {
+    Some(match kind {
+        "ip-out-of-bounds" => RuntimeError::IpOutOfBounds { ip: field(fields, "ip")? },
+        "bad-jump" => RuntimeError::BadJumpTarget { from: field(fields, "from")?, to: field(fields, "to")? },
+        "unknown-label" => RuntimeError::UnknownLabel { line: field(fields, "line")?, label: field(fields, "label")? },
+        "input-exhausted" => RuntimeError::InputExhausted { line: field(fields, "line")? },
+        "wrong-output" => RuntimeError::WrongOutput {
+            line: field(fields, "line")?,
+            got: field(fields, "got")?,
+            expected: match field::<String>(fields, "expected")?.as_str() {
+                "none" => None,
+                n => Some(n.parse().ok()?),
+            },
+        },
+        _ => return None,
+    })
+}
+
+/// the value of a `key=value` field
+fn field<T: FromStr>(fields: &[&str], key: &str) -> Option<T> {
+    fields.iter().find_map(|f| f.strip_prefix(key)?.strip_prefix('='))?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let replay = Replay::record("1", "RCW\nRCW\nMOV 1 M\nL:\nJMP L").unwrap();
+        assert_eq!(replay.outcomes, vec![Outcome::Cleared(11)]);
+        let parsed: Replay = replay.to_string().parse().unwrap();
+        assert_eq!(parsed, replay);
+        parsed.verify().unwrap();
+
+        let mut tampered = parsed.clone();
+        tampered.program = "NOP".to_owned();
+        assert!(tampered.verify().is_err());
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let outcomes = [
+            Outcome::Cleared(11),
+            Outcome::Halted(6),
+            Outcome::Timeout(1000),
+            Outcome::Error(2, RuntimeError::IpOutOfBounds { ip: 1 }),
+            Outcome::Error(2, RuntimeError::BadJumpTarget { from: 1, to: -3 }),
+            Outcome::Error(2, RuntimeError::UnknownLabel { line: 0, label: "NOWHERE".to_owned() }),
+            Outcome::Error(2, RuntimeError::InputExhausted { line: 2 }),
+            Outcome::Error(2, RuntimeError::WrongOutput { line: 3, got: -4, expected: Some(8) }),
+            Outcome::Error(2, RuntimeError::WrongOutput { line: 3, got: 4, expected: None }),
+        ];
+        for outcome in outcomes.iter() {
+            assert_eq!(&parse_outcome(&write_outcome(outcome)).unwrap(), outcome);
+        }
+        assert!(parse_outcome("error 2 input-exhausted line=-1").is_err());
+        assert!(parse_outcome("error 2 line 1 reads past the end of the input").is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::crab::{Crab, RuntimeError};
+use crate::level::{self, Grid, Level};
+use crate::trace::Trace;
+use std::fmt;
+
+/// cycles a program may take on levels that do not set their own limit
+pub const CYCLE_LIMIT: usize = 1_000_000;
+
+/// how a headless run ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// every tile was collected, after this many cycles
+    Cleared(usize),
+    /// the program ran off its end before clearing the level
+    Halted(usize),
+    /// the program crashed
+    Error(usize, RuntimeError),
+    /// the level was not cleared within the cycle limit
+    Timeout(usize),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Cleared(cycles) => write!(f, "cleared {}", cycles),
+            Outcome::Halted(cycles) => write!(f, "halted {}", cycles),
+            Outcome::Error(cycles, e) => write!(f, "error {} {}", cycles, e),
+            Outcome::Timeout(cycles) => write!(f, "timeout {}", cycles),
+        }
+    }
+}
+
+/// a crab running a program on a level, without any drawing
+///
+/// the game and the command line both step through this so they agree on every cycle
+pub struct Sim {
+    pub crab: Crab,
+    pub grid: Grid,
+    /// number of cycles executed since the code was loaded
+    pub cycle: usize,
+    /// source text of every instruction
+    pub source: Vec<String>,
+    /// per cycle records, only kept when set
+    pub trace: Option<Trace>,
+    /// values the program has to write with `OUT`, in order
+    pub expected: Vec<i32>,
+    /// tiles left on `grid` row by row, so clearing and sensing do not have to scan it
+    tiles: Vec<(i32, i32)>,
+    /// what the sensor reads, only looked up again when the crab moves or collects a tile
+    sensed: (i32, i32),
+}
+
+impl Sim {
+    pub fn new(grid: Grid) -> Self {
+        let mut sim = Self {
+            crab: Crab::new(),
+            grid: vec![],
+            cycle: 0,
+            source: vec![],
+            trace: None,
+            expected: vec![],
+            tiles: vec![],
+            sensed: (0, 0),
+        };
+        sim.set_grid(grid);
+        sim
+    }
+
+    /// returns the line of the first instruction that does not parse
+    pub fn load_code(&mut self, code: &str) -> Result<(), usize> {
+        self.crab.load_code(code)?;
+        self.source = code.lines().map(|l| l.trim().to_owned()).collect();
+        Ok(())
+    }
+
+    /// executes one cycle and collects the tile under the crab
+    ///
+    /// a crab that already halted is left alone and the cycle is not counted
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        if self.crab.stopped() {
+            return self.crab.step();
+        }
+        let ip = self.crab.ip;
+        let pos = (self.crab.pos_x, self.crab.pos_y);
+        let written = self.crab.output.len();
+        let mut res = self.crab.step();
+        if res.is_ok() && self.crab.output.len() > written {
+            let got = self.crab.output[written];
+            if self.expected.get(written) != Some(&got) {
+                res = Err(RuntimeError::WrongOutput { line: ip, got, expected: self.expected.get(written).cloned() });
+            }
+        }
+        self.cycle += 1;
+        let collected = self.collect();
+        if !collected.is_empty() || (self.crab.pos_x, self.crab.pos_y) != pos {
+            self.sensed = self.crab.nearest(&self.tiles);
+        }
+        // the program may have written to H or V
+        self.crab.set_sensor(self.sensed);
+        if let (Ok(_), Some(trace)) = (&res, self.trace.as_mut()) {
+            trace.record(self.cycle, ip, &self.source[ip], &self.crab, collected);
+        }
+        res
+    }
+
+    fn collect(&mut self) -> Vec<(i32, i32)> {
+        let (x, y) = (self.crab.pos_x, self.crab.pos_y);
+        if x < 0 || y < 0 {
+            return vec![];
+        }
+        match self.grid.get_mut(y as usize).and_then(|row| row.get_mut(x as usize)) {
+            Some(tile) if *tile => {
+                *tile = false;
+                self.tiles.retain(|t| *t != (x, y));
+                vec![(x, y)]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// every tile collected and all of the expected output written
+    pub fn cleared(&self) -> bool {
+        self.tiles.is_empty() && self.crab.output.len() == self.expected.len()
+    }
+
+    /// sets the values `IN` reads and the ones `OUT` has to write
+    pub fn set_streams(&mut self, input: Vec<i32>, expected: Vec<i32>) {
+        self.crab.input = input;
+        self.expected = expected;
+    }
+
+    /// replaces the tiles, use this rather than assigning `grid`
+    pub fn set_grid(&mut self, grid: Grid) {
+        self.tiles = level::tiles(&grid);
+        self.grid = grid;
+        self.sensed = self.crab.nearest(&self.tiles);
+        self.crab.set_sensor(self.sensed);
+    }
+
+    /// runs until the level is cleared, the program stops or `limit` cycles have passed
+    pub fn run(&mut self, limit: usize) -> Outcome {
+        self.run_with(limit, |_| {})
+    }
+
+    /// like `run`, also returning the tiles the crab walks over in order, starting with the one it is on
+    pub fn run_path(&mut self, limit: usize) -> (Vec<(i32, i32)>, Outcome) {
+        let mut path = vec![(self.crab.pos_x, self.crab.pos_y)];
+        let outcome = self.run_with(limit, |sim| {
+            let pos = (sim.crab.pos_x, sim.crab.pos_y);
+            if path.last() != Some(&pos) {
+                path.push(pos);
+            }
+        });
+        (path, outcome)
+    }
+
+    /// `run`, calling `each` after every cycle
+    fn run_with(&mut self, limit: usize, mut each: impl FnMut(&Sim)) -> Outcome {
+        while !self.cleared() {
+            if self.crab.stopped() {
+                return Outcome::Halted(self.cycle);
+            }
+            if self.cycle >= limit {
+                return Outcome::Timeout(self.cycle);
+            }
+            let res = self.step();
+            each(self);
+            if let Err(e) = res {
+                return Outcome::Error(self.cycle, e);
+            }
+        }
+        Outcome::Cleared(self.cycle)
+    }
+
+    /// puts the crab back to the start on a fresh copy of `grid`, keeping the code
+    pub fn reset(&mut self, grid: Grid) {
+        self.crab.reset();
+        self.set_grid(grid);
+        self.cycle = 0;
+        if let Some(trace) = self.trace.as_mut() {
+            trace.clear();
+        }
+    }
+}
+
+/// runs `code` on every variant of `level` in turn, each from a fresh start with `limit` cycles
+///
+/// returns the line of the first instruction that does not parse
+pub fn run_level(level: &Level, code: &str, limit: usize) -> Result<Vec<Outcome>, usize> {
+    let mut outcomes = vec![];
+    for grid in &level.grids {
+        let mut sim = Sim::new(grid.clone());
+        sim.set_streams(level.input.clone(), level.output.clone());
+        sim.load_code(code)?;
+        outcomes.push(sim.run(limit));
+    }
+    Ok(outcomes)
+}
+
+/// one outcome per variant, separated by commas
+pub fn describe(outcomes: &[Outcome]) -> String {
+    outcomes.iter().map(|o| o.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// true if every variant was cleared
+pub fn all_cleared(outcomes: &[Outcome]) -> bool {
+    outcomes.iter().all(|o| matches!(o, Outcome::Cleared(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{self, Profile, Verdict};
+
+    #[test]
+    fn test_run() {
+        let mut sim = Sim::new(level::levels()[0].grids[0].clone());
+        sim.load_code("MOV 1 M\nNOP\nNOP\nNOP\nNOP\nNOP").unwrap();
+        assert_eq!(sim.run(100), Outcome::Halted(6));
+
+        let mut sim = Sim::new(level::levels()[0].grids[0].clone());
+        sim.load_code("NOP\nJMP NOWHERE").unwrap();
+        assert_eq!(sim.run(100), Outcome::Error(2, RuntimeError::UnknownLabel { line: 1, label: "NOWHERE".to_owned() }));
+
+        let mut sim = Sim::new(level::levels()[0].grids[0].clone());
+        sim.load_code("L:\nJMP L").unwrap();
+        assert_eq!(sim.run(100), Outcome::Timeout(100));
+
+        // R = 2 makes a positive M walk towards larger x
+        let mut sim = Sim::new(level::levels()[0].grids[0].clone());
+        sim.trace = Some(Trace::new());
+        sim.load_code("RCW\nRCW\nMOV 1 M\nL:\nJMP L").unwrap();
+        assert_eq!(sim.run(100), Outcome::Cleared(11));
+        let trace = sim.trace.unwrap();
+        let collected: Vec<_> = trace.records.iter().flat_map(|r| r.collected.clone()).collect();
+        assert_eq!(collected, vec![(4, 3), (5, 3), (6, 3), (7, 3)]);
+
+        let mut sim = Sim::new(level::levels()[0].grids[0].clone());
+        sim.load_code("RCW\nRCW\nMOV 1 M\nL:\nJMP L").unwrap();
+        assert_eq!(sim.run_path(100), (vec![(3, 3), (4, 3), (5, 3), (6, 3), (7, 3)], Outcome::Cleared(11)));
+    }
+
+    #[test]
+    fn test_variants() {
+        let level = &level::levels()[8];
+        let seek = "L:\nMOV 0 A\nSUB H A\nMOV A M\nJMP L";
+        let outcomes = run_level(level, seek, level.cycle_limit).unwrap();
+        assert_eq!(outcomes, vec![Outcome::Cleared(8), Outcome::Cleared(9), Outcome::Cleared(7)]);
+        assert!(all_cleared(&outcomes));
+
+        // a program that ignores the sensor walks the same path on every variant,
+        // and no path reaches the tiles of all of them within the cycle limit
+        let mut union = level.grids[0].clone();
+        for (x, y) in level.grids.iter().flat_map(level::tiles) {
+            union[y as usize][x as usize] = true;
+        }
+        match solver::solve(&union, Profile::full(), solver::MAX_STATES).unwrap() {
+            Verdict::Solvable { cycles, .. } => assert!(cycles > level.cycle_limit),
+            verdict => panic!("{}", verdict),
+        }
+    }
+
+    #[test]
+    fn test_streams() {
+        let level = level::parse("______\n\n\n\n\n\nin: 3 -1\nout: 6 -2\n");
+        let double = "L:\nIN A\nADD A A\nOUT A\nJMP L";
+        assert_eq!(run_level(&level, double, 100), Ok(vec![Outcome::Cleared(9)]));
+        let copy = "L:\nIN A\nOUT A\nJMP L";
+        assert_eq!(run_level(&level, copy, 100), Ok(vec![
+            Outcome::Error(3, RuntimeError::WrongOutput { line: 2, got: 3, expected: Some(6) }),
+        ]));
+    }
+}
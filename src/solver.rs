@@ -0,0 +1,322 @@
+//! decides whether a level can be cleared and how fast, independent of any program
+//!
+//! every cycle the motor moves the crab with the `M` and `R` it had when the
+//! cycle started, then the instruction may change one of them. Only the sign of
+//! `M` matters, so the search runs over position × rotation × motor sign × tiles
+//! collected. Any path through that space is a straight line program, which
+//! makes the shortest path the fewest cycles any program can take.
+
+use crate::crab::{DIRS, INIT_X, INIT_Y};
+use crate::level::{Grid, Level};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+/// how far outside the grid the crab may wander, enough to stop after overshooting an edge
+const MARGIN: i32 = 2;
+/// states explored before giving up, by default
+pub const MAX_STATES: usize = 5_000_000;
+
+/// the instructions a program may use to steer the crab
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    pub rcw: bool,
+    pub rcc: bool,
+    /// whether `M` may be negative
+    pub reverse: bool,
+    /// most `RCW` and `RCC` a program may execute, unlimited when None
+    pub max_turns: Option<u32>,
+}
+
+impl Profile {
+    pub fn full() -> Self {
+        Profile { rcw: true, rcc: true, reverse: true, max_turns: None }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+    /// `full`, or a comma separated list of `rcw`, `rcc`, `reverse` and `turns=<n>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("full") {
+            return Ok(Profile::full());
+        }
+        let mut profile = Profile { rcw: false, rcc: false, reverse: false, max_turns: None };
+        for feature in s.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            match feature.to_lowercase().as_str() {
+                "rcw" => profile.rcw = true,
+                "rcc" => profile.rcc = true,
+                "reverse" => profile.reverse = true,
+                f if f.starts_with("turns=") => profile.max_turns = Some(f["turns=".len()..].parse()
+                    .map_err(|_| format!("invalid turn count {}", feature))?),
+                _ => return Err(format!("unknown ISA feature {}", feature)),
+            }
+        }
+        Ok(profile)
+    }
+}
+
+/// what the instruction of one cycle does to the crab
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Wait,
+    /// sets `M` to -1, 0 or 1
+    Motor(i32),
+    Rcw,
+    Rcc,
+}
+
+impl Action {
+    /// the instruction that performs this action
+    pub fn instruction(self) -> String {
+        match self {
+            Action::Wait => "NOP".to_owned(),
+            Action::Motor(m) => format!("MOV {} M", m),
+            Action::Rcw => "RCW".to_owned(),
+            Action::Rcc => "RCC".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct State {
+    x: i32,
+    y: i32,
+    r: i32,
+    m: i32,
+    /// bit `i` is set once target `i` was collected
+    collected: u64,
+    /// rotations so far, only counted when the profile limits them
+    turns: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// cleared in the fewest possible cycles by doing `actions`, one per cycle
+    Solvable { cycles: usize, actions: Vec<Action> },
+    /// no program can clear the level
+    Unsolvable,
+    /// gave up after exploring this many states
+    Undecided(usize),
+}
+
+impl Verdict {
+    /// a straight line program that reaches the minimum, if the level is solvable
+    pub fn program(&self) -> Option<String> {
+        match self {
+            Verdict::Solvable { actions, .. } => {
+                Some(actions.iter().map(|a| a.instruction()).collect::<Vec<_>>().join("\n"))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Solvable { cycles, .. } => write!(f, "solvable in {} cycles", cycles),
+            Verdict::Unsolvable => write!(f, "unsolvable"),
+            Verdict::Undecided(states) => write!(f, "undecided after {} states", states),
+        }
+    }
+}
+
+/// false for levels with an input or an expected output, whose programs have to
+/// read and write values that a path through the grid knows nothing about
+pub fn supports(level: &Level) -> bool {
+    level.input.is_empty() && level.output.is_empty()
+}
+
+/// a breadth first search that can be run a few states at a time, so the game can spread it over frames
+pub struct Search {
+    targets: Vec<(i32, i32)>,
+    /// `collected` once every target is
+    all: u64,
+    width: i32,
+    height: i32,
+    profile: Profile,
+    actions: Vec<Action>,
+    max_states: usize,
+    /// every state seen, with the state and action it was first reached by
+    seen: HashMap<State, Option<(State, Action)>>,
+    queue: VecDeque<State>,
+    /// states taken off the queue so far
+    expanded: usize,
+    verdict: Option<Verdict>,
+}
+
+impl Search {
+    pub fn new(grid: &Grid, profile: Profile, max_states: usize) -> Result<Search, String> {
+        let targets: Vec<(i32, i32)> = grid.iter().enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().filter(|(_, t)| **t).map(move |(x, _)| (x as i32, y as i32)))
+            .collect();
+        if targets.len() > 64 {
+            return Err(format!("{} targets, the solver handles at most 64", targets.len()));
+        }
+        let all = if targets.len() == 64 { !0 } else { (1u64 << targets.len()) - 1 };
+
+        let mut actions = vec![Action::Wait, Action::Motor(0), Action::Motor(1)];
+        if profile.reverse {
+            actions.push(Action::Motor(-1));
+        }
+        if profile.rcw {
+            actions.push(Action::Rcw);
+        }
+        if profile.rcc {
+            actions.push(Action::Rcc);
+        }
+
+        let mut search = Search {
+            targets,
+            all,
+            width: grid.iter().map(|row| row.len()).max().unwrap_or(0) as i32,
+            height: grid.len() as i32,
+            profile,
+            actions,
+            max_states,
+            seen: HashMap::new(),
+            queue: VecDeque::new(),
+            expanded: 0,
+            verdict: None,
+        };
+        if all == 0 {
+            search.verdict = Some(Verdict::Solvable { cycles: 0, actions: vec![] });
+        } else {
+            let start = State { x: INIT_X, y: INIT_Y, r: 0, m: 0, collected: 0, turns: 0 };
+            search.seen.insert(start, None);
+            search.queue.push_back(start);
+        }
+        Ok(search)
+    }
+
+    /// takes up to `states` more states off the queue, returns the verdict once there is one
+    pub fn run(&mut self, states: usize) -> Option<&Verdict> {
+        for _ in 0..states {
+            if self.verdict.is_some() {
+                break;
+            }
+            match self.queue.pop_front() {
+                Some(state) => {
+                    self.expanded += 1;
+                    self.expand(state);
+                }
+                None => self.verdict = Some(Verdict::Unsolvable),
+            }
+        }
+        self.verdict.as_ref()
+    }
+
+    pub fn expanded(&self) -> usize {
+        self.expanded
+    }
+
+    fn expand(&mut self, state: State) {
+        let (dx, dy) = DIRS[state.r as usize];
+        let (x, y) = (state.x + dx * state.m, state.y + dy * state.m);
+        if x < -MARGIN || y < -MARGIN || x >= self.width + MARGIN || y >= self.height + MARGIN {
+            return;
+        }
+        let collected = match self.targets.iter().position(|t| *t == (x, y)) {
+            Some(i) => state.collected | 1 << i,
+            None => state.collected,
+        };
+        for action in &self.actions {
+            let (r, m) = match *action {
+                Action::Wait => (state.r, state.m),
+                Action::Motor(m) if m == state.m => continue,
+                Action::Motor(m) => (state.r, m),
+                Action::Rcw => ((state.r + 1) % 4, state.m),
+                Action::Rcc => ((state.r + 3) % 4, state.m),
+            };
+            let turns = match (*action, self.profile.max_turns) {
+                (Action::Rcw, Some(max)) | (Action::Rcc, Some(max)) if state.turns == max => continue,
+                (Action::Rcw, Some(_)) | (Action::Rcc, Some(_)) => state.turns + 1,
+                _ => state.turns,
+            };
+            let next = State { x, y, r, m, collected, turns };
+            if self.seen.contains_key(&next) {
+                continue;
+            }
+            self.seen.insert(next, Some((state, *action)));
+            if collected == self.all {
+                let actions = path(&self.seen, next);
+                self.verdict = Some(Verdict::Solvable { cycles: actions.len(), actions });
+                return;
+            }
+            if self.seen.len() >= self.max_states {
+                self.verdict = Some(Verdict::Undecided(self.seen.len()));
+                return;
+            }
+            self.queue.push_back(next);
+        }
+    }
+}
+
+/// breadth first search for the fewest cycles that collect every tile of `grid`
+pub fn solve(grid: &Grid, profile: Profile, max_states: usize) -> Result<Verdict, String> {
+    let mut search = Search::new(grid, profile, max_states)?;
+    Ok(search.run(usize::MAX).cloned().unwrap())
+}
+
+/// the actions that lead from the start to `end`
+fn path(seen: &HashMap<State, Option<(State, Action)>>, end: State) -> Vec<Action> {
+    let mut actions = vec![];
+    let mut state = end;
+    while let Some(Some((prev, action))) = seen.get(&state) {
+        actions.push(*action);
+        state = *prev;
+    }
+    actions.reverse();
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level;
+    use crate::sim::{Outcome, Sim};
+
+    #[test]
+    fn test_solve() {
+        let levels = level::levels();
+        let verdict = solve(&levels[0].grids[0], Profile::full(), MAX_STATES).unwrap();
+        // backwards while facing down walks right, the motor starts a cycle late
+        assert_eq!(verdict, Verdict::Solvable {
+            cycles: 5,
+            actions: vec![Action::Motor(-1), Action::Wait, Action::Wait, Action::Wait, Action::Wait],
+        });
+
+        for level in &levels[..6] {
+            let verdict = solve(&level.grids[0], Profile::full(), MAX_STATES).unwrap();
+            let cycles = match &verdict {
+                Verdict::Solvable { cycles, .. } => *cycles,
+                v => panic!("{}", v),
+            };
+            let mut sim = Sim::new(level.grids[0].clone());
+            sim.load_code(&verdict.program().unwrap()).unwrap();
+            assert_eq!(sim.run(cycles), Outcome::Cleared(cycles));
+        }
+
+        // moving left only never reaches the tiles right of the start
+        let left = Profile { rcw: false, rcc: false, reverse: false, max_turns: None };
+        assert_eq!(solve(&levels[0].grids[0], left, MAX_STATES), Ok(Verdict::Unsolvable));
+        // the L shaped level needs a turn, with one it takes the full ISA's time
+        let no_turns = Profile { max_turns: Some(0), ..Profile::full() };
+        assert_eq!(solve(&levels[2].grids[0], no_turns, MAX_STATES), Ok(Verdict::Unsolvable));
+        let one_turn = Profile { max_turns: Some(1), ..Profile::full() };
+        assert_eq!(solve(&levels[2].grids[0], one_turn, MAX_STATES).unwrap().to_string(), "solvable in 6 cycles");
+
+        // a search run in small pieces comes to the same verdict
+        let mut search = Search::new(&levels[2].grids[0], one_turn, MAX_STATES).unwrap();
+        let mut runs = 1;
+        while search.run(10).is_none() {
+            runs += 1;
+        }
+        assert!(runs > 1);
+        assert_eq!(search.run(10).unwrap().to_string(), "solvable in 6 cycles");
+
+        assert!(supports(&levels[0]));
+        assert!(!supports(&level::parse("______\n\n\n\n\n\nin: 3\nout: 6\n")));
+    }
+}
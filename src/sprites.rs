@@ -6,6 +6,8 @@ pub struct Sprites {
     pub anims: HashMap<String, Animation>,
     pub sounds: HashMap<String, Sound>,
     strings: HashMap<String, Image>,
+    /// text in other colors than black, keyed by the color's bytes
+    colored: HashMap<(String, [u8; 4]), Image>,
 }
 
 impl Sprites {
@@ -83,7 +85,7 @@ impl Sprites {
 
         let ret = fut_anim.join3(fut_items, fut_sounds)
             .map(|(anims,items,sounds)| Sprites {
-                items, anims, sounds, strings: HashMap::new(),
+                items, anims, sounds, strings: HashMap::new(), colored: HashMap::new(),
             });
         ret
     }
@@ -144,4 +146,19 @@ impl Sprites {
         }
     }
 
+    pub fn render_colored(&mut self, s: &str, color: Color) -> Image {
+        let byte = |c: f32| (c * 255.) as u8;
+        let key = (s.to_owned(), [byte(color.r), byte(color.g), byte(color.b), byte(color.a)]);
+        if let Some(img) = self.colored.get(&key) {
+            return img.to_owned();
+        }
+        let img = Font::from_slice(include_bytes!("../static/fonts/VGATypewriter.ttf"))
+                .and_then(move |font| {
+                    let style = FontStyle::new(90.0, color);
+                    font.render(s, &style)
+                }).unwrap();
+        self.colored.insert(key, img.clone());
+        img
+    }
+
 }
\ No newline at end of file
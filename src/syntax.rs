@@ -0,0 +1,219 @@
+//! splits lines of a program into tokens and parses them, shared by the assembler and the editor
+
+use crate::crab::{OpCode, Register};
+use crate::isa::{self, Arg, Instruction, Operand};
+use std::fmt;
+
+/// the operands of an instruction, None if there is no such instruction
+pub fn operands(mnemonic: &str) -> Option<&'static [Operand]> {
+    isa::find(mnemonic).map(|instr| instr.operands)
+}
+
+/// an instruction with its operands, such as `ADD <int|reg> <reg>`
+pub fn signature(mnemonic: &str) -> Option<String> {
+    isa::find(mnemonic).map(Instruction::signature)
+}
+
+/// jumps take a label as their operand
+fn is_jump(mnemonic: &str) -> bool {
+    operands(mnemonic).unwrap_or(&[]).contains(&Operand::Label)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Mnemonic,
+    Register,
+    Number,
+    /// a label definition or the target of a jump
+    Label,
+    /// from `#` to the end of the line
+    Comment,
+    /// fits nowhere, such as an unknown instruction
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    /// byte offset in the line
+    pub start: usize,
+    pub kind: TokenKind,
+}
+
+impl<'a> Token<'a> {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    fn error(&self, message: String) -> SyntaxError {
+        SyntaxError { start: self.start, end: self.end(), message }
+    }
+}
+
+/// why a line does not assemble, `start..end` is the part of the line at fault
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+fn is_number(word: &str) -> bool {
+    let digits = word.strip_prefix('-').unwrap_or(word);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// splits a line at whitespace, judging each word by its shape and the instruction before it
+pub fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut rest = line;
+    while let Some(skip) = rest.find(|c: char| !c.is_whitespace()) {
+        let start = line.len() - rest.len() + skip;
+        let word = &line[start..];
+        let (text, kind) = if word.starts_with('#') {
+            (word.trim_end(), TokenKind::Comment)
+        } else {
+            let text = &word[..word.find(char::is_whitespace).unwrap_or(word.len())];
+            let kind = match tokens.first() {
+                None if text.ends_with(':') => TokenKind::Label,
+                None if isa::find(text).is_some() => TokenKind::Mnemonic,
+                None => TokenKind::Error,
+                Some(first) if is_jump(first.text) => TokenKind::Label,
+                Some(_) if is_number(text) => TokenKind::Number,
+                Some(_) if text.parse::<Register>().is_ok() => TokenKind::Register,
+                Some(_) => TokenKind::Error,
+            };
+            (text, kind)
+        };
+        tokens.push(Token { text, start, kind });
+        rest = &line[start + text.len()..];
+    }
+    tokens
+}
+
+/// operands of an instruction, the mnemonic itself is not included
+struct Operands<'a, 'b> {
+    mnemonic: &'b Token<'a>,
+    tokens: &'b [Token<'a>],
+    /// where a missing operand would go
+    end: usize,
+}
+
+impl<'a, 'b> Operands<'a, 'b> {
+    fn get(&self, i: usize, what: &str) -> Result<&'b Token<'a>, SyntaxError> {
+        self.tokens.get(i).ok_or_else(|| SyntaxError {
+            start: self.end,
+            end: self.end,
+            message: format!("{} needs {}", self.mnemonic.text, what),
+        })
+    }
+
+    fn register(&self, i: usize) -> Result<Register, SyntaxError> {
+        let token = self.get(i, "a register")?;
+        token.text.parse().map_err(|_| token.error(format!("{} is not a register", token.text)))
+    }
+
+    fn number(token: &Token) -> Result<i32, SyntaxError> {
+        token.text.parse().map_err(|_| token.error(format!("{} is too large", token.text)))
+    }
+
+    /// a number or a register
+    fn value(&self, i: usize) -> Result<Arg, SyntaxError> {
+        let token = self.get(i, "a number or register")?;
+        match token.kind {
+            TokenKind::Number => Self::number(token).map(Arg::Num),
+            TokenKind::Register => Ok(Arg::Reg(token.text.parse().unwrap())),
+            _ => Err(token.error(format!("{} is not a number or register", token.text))),
+        }
+    }
+
+    fn label(&self, i: usize) -> Result<String, SyntaxError> {
+        Ok(self.get(i, "a label")?.text.to_owned())
+    }
+
+    fn parse(&self, i: usize, operand: Operand) -> Result<Arg, SyntaxError> {
+        match operand {
+            Operand::Value => self.value(i),
+            Operand::Register => self.register(i).map(Arg::Reg),
+            Operand::Label => self.label(i).map(Arg::Label),
+        }
+    }
+
+    /// fails if anything follows the first `count` operands
+    fn done(&self, count: usize) -> Result<(), SyntaxError> {
+        match self.tokens.get(count) {
+            Some(token) if self.mnemonic.kind == TokenKind::Label => {
+                Err(token.error("labels must be on a line of their own".to_owned()))
+            }
+            Some(token) => Err(token.error(format!("{} is one operand too many", token.text))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// parses one line, blank lines and comments after an instruction are ignored
+pub fn parse_line(line: &str) -> Result<OpCode, SyntaxError> {
+    let tokens: Vec<_> = tokenize(line).into_iter().filter(|t| t.kind != TokenKind::Comment).collect();
+    let first = match tokens.first() {
+        Some(first) => first,
+        None => return Ok(OpCode::COMMENT(line.to_string())),
+    };
+    let ops = Operands { mnemonic: first, tokens: &tokens[1..], end: line.trim_end().len() };
+    if first.kind == TokenKind::Label {
+        ops.done(0)?;
+        return Ok(OpCode::LABEL(first.text[..first.text.len() - 1].to_owned()));
+    }
+    let instr = isa::find(first.text).ok_or_else(|| first.error(format!("{} is not an instruction", first.text)))?;
+    let args = instr.operands.iter().enumerate()
+        .map(|(i, &operand)| ops.parse(i, operand))
+        .collect::<Result<Vec<_>, _>>()?;
+    ops.done(args.len())?;
+    Ok(OpCode::from_parts(instr.mnemonic, &args).expect("every instruction in the table has an opcode"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let kinds = |line| tokenize(line).iter().map(|t| (t.text, t.kind)).collect::<Vec<_>>();
+        assert_eq!(kinds("  MOV -1 M # go"), vec![
+            ("MOV", TokenKind::Mnemonic),
+            ("-1", TokenKind::Number),
+            ("M", TokenKind::Register),
+            ("# go", TokenKind::Comment),
+        ]);
+        assert_eq!(kinds("JEZ A"), vec![("JEZ", TokenKind::Mnemonic), ("A", TokenKind::Label)]);
+        assert_eq!(kinds("LOOP_1:"), vec![("LOOP_1:", TokenKind::Label)]);
+        assert_eq!(kinds("JNL X"), vec![("JNL", TokenKind::Error), ("X", TokenKind::Error)]);
+        assert_eq!(tokenize(" ADD 1  A")[2].start, 8);
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(signature("ADD").unwrap(), "ADD <int|reg> <reg>");
+        assert_eq!(signature("JLZ").unwrap(), "JLZ <label>");
+        assert_eq!(signature("JNL"), None);
+    }
+
+    #[test]
+    fn test_parse_line() {
+        assert!(matches!(parse_line("MOV 1 A # one"), Ok(OpCode::MOVI(1, Register::A))));
+        assert!(matches!(parse_line("# just a comment"), Ok(OpCode::COMMENT(_))));
+        assert!(matches!(parse_line("  "), Ok(OpCode::COMMENT(_))));
+        let error = |line| parse_line(line).unwrap_err();
+        assert_eq!(error("MOV 1 X"), SyntaxError { start: 6, end: 7, message: "X is not a register".to_owned() });
+        assert_eq!(error("ADD 1 "), SyntaxError { start: 5, end: 5, message: "ADD needs a register".to_owned() });
+        assert_eq!(error("NOP A").message, "A is one operand too many");
+        assert_eq!(error("L: NOP"), SyntaxError { start: 3, end: 6, message: "labels must be on a line of their own".to_owned() });
+        assert_eq!(error("JNL X").message, "JNL is not an instruction");
+        assert_eq!(error("OUT 99999999999").message, "99999999999 is too large");
+    }
+}
@@ -0,0 +1,176 @@
+//! finds small programs that clear a level by trying every one of them
+//!
+//! programs are built from the instructions that steer the crab plus `JRO`
+//! jumping backwards, which is enough for every loop without labels
+
+use crate::level::{self, Level};
+use crate::sim::{Outcome, Sim};
+use std::str::FromStr;
+
+/// longest programs tried by default, the count grows about tenfold per instruction
+pub const MAX_LEN: usize = 5;
+
+const STEER: [&str; 6] = ["NOP", "MOV 1 M", "MOV 0 M", "MOV -1 M", "RCW", "RCC"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Goal {
+    /// fewest instructions, then fewest cycles
+    Shortest,
+    /// fewest cycles, then fewest instructions
+    Fastest,
+}
+
+impl FromStr for Goal {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "shortest" => Ok(Goal::Shortest),
+            "fastest" => Ok(Goal::Fastest),
+            _ => Err(format!("unknown goal {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub program: String,
+    pub instructions: usize,
+    /// summed over every variant
+    pub cycles: usize,
+}
+
+/// a search that can be run a few programs at a time, so the game can spread it over frames
+pub struct Synthesis {
+    level: Level,
+    sim: Sim,
+    max_len: usize,
+    goal: Goal,
+    /// per variant
+    limit: usize,
+    /// the choice on every line of the next program to try, see `choice`
+    next: Vec<usize>,
+    best: Option<Found>,
+    done: bool,
+}
+
+/// the `i`th instruction a line may hold, the jumps come last and go back by `i - STEER.len()` lines
+fn choice(i: usize) -> String {
+    match STEER.get(i) {
+        Some(steer) => steer.to_string(),
+        None => format!("JRO {}", -((i - STEER.len()) as i32)),
+    }
+}
+
+/// instructions that may go on line `line`, jumps only reach lines before it
+fn choice_count(line: usize) -> usize {
+    STEER.len() + line + 1
+}
+
+impl Synthesis {
+    /// programs never use `IN` or `OUT`, so nothing is found for levels that expect output
+    pub fn new(level: &Level, max_len: usize, goal: Goal, limit: usize) -> Synthesis {
+        let mut sim = Sim::new(level.grids[0].clone());
+        sim.set_streams(level.input.clone(), level.output.clone());
+        let mut search = Synthesis {
+            level: level.clone(),
+            sim,
+            max_len,
+            goal,
+            limit,
+            next: vec![0],
+            best: None,
+            done: max_len == 0,
+        };
+        if level.grids.iter().all(level::cleared) && level.output.is_empty() {
+            search.best = Some(Found { program: String::new(), instructions: 0, cycles: 0 });
+            search.done = true;
+        }
+        search
+    }
+
+    /// tries programs until about `cycles` cycles were run, true once the search is over
+    pub fn run(&mut self, cycles: usize) -> bool {
+        let mut spent = 0;
+        while !self.done && spent < cycles {
+            let program: Vec<String> = self.next.iter().map(|i| choice(*i)).collect();
+            spent += self.try_program(&program);
+            self.advance();
+        }
+        self.done
+    }
+
+    /// the best program so far, the best there is once `run` returned true
+    pub fn best(&self) -> Option<&Found> {
+        self.best.as_ref()
+    }
+
+    /// moves on to the next program, counting like an odometer with the last line turning fastest
+    fn advance(&mut self) {
+        for line in (0..self.next.len()).rev() {
+            self.next[line] += 1;
+            if self.next[line] < choice_count(line) {
+                return;
+            }
+            self.next[line] = 0;
+        }
+        // every program of this length was tried
+        let len = self.next.len();
+        if len == self.max_len || (self.goal == Goal::Shortest && self.best.is_some()) {
+            self.done = true;
+        } else {
+            self.next = vec![0; len + 1];
+        }
+    }
+
+    /// runs a program on every variant, returns the cycles it took
+    fn try_program(&mut self, program: &[String]) -> usize {
+        // lengths are tried in order, so a run only has to beat the best cycle count
+        let mut budget = match &self.best {
+            Some(best) => best.cycles - 1,
+            None => self.limit * self.level.grids.len(),
+        };
+        let code = program.join("\n");
+        self.sim.load_code(&code).unwrap();
+        let mut cycles = 0;
+        for grid in &self.level.grids {
+            self.sim.reset(grid.clone());
+            let outcome = self.sim.run(self.limit.min(budget));
+            cycles += self.sim.cycle;
+            match outcome {
+                Outcome::Cleared(n) => budget -= n,
+                _ => return cycles,
+            }
+        }
+        self.best = Some(Found { program: code, instructions: program.len(), cycles });
+        cycles
+    }
+}
+
+/// the best program of at most `max_len` instructions that clears every variant within `limit` cycles each
+pub fn synthesize(level: &Level, max_len: usize, goal: Goal, limit: usize) -> Option<Found> {
+    let mut search = Synthesis::new(level, max_len, goal, limit);
+    search.run(usize::MAX);
+    search.best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthesize() {
+        let level = &level::levels()[0];
+        let found = synthesize(level, 3, Goal::Shortest, 100).unwrap();
+        assert_eq!(found, Found { program: "MOV -1 M\nJRO 0".to_owned(), instructions: 2, cycles: 5 });
+        assert_eq!(synthesize(level, 1, Goal::Fastest, 100), None);
+
+        // a search run in small pieces finds the same program
+        let mut search = Synthesis::new(level, 3, Goal::Shortest, 100);
+        let mut runs = 1;
+        while !search.run(10) {
+            runs += 1;
+        }
+        assert!(runs > 1);
+        assert_eq!(search.best(), Some(&found));
+    }
+}
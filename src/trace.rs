@@ -0,0 +1,172 @@
+use crate::crab::{Crab, Register, REGISTERS};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    /// one json object per line
+    Jsonl,
+    Csv,
+}
+
+impl TraceFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TraceFormat::Jsonl => "jsonl",
+            TraceFormat::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "jsonl" | "json" => Ok(TraceFormat::Jsonl),
+            "csv" => Ok(TraceFormat::Csv),
+            _ => Err(format!("unknown trace format {}", s)),
+        }
+    }
+}
+
+/// state after one executed cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    pub cycle: usize,
+    /// instruction pointer of the executed instruction
+    pub ip: usize,
+    /// source text of the executed instruction
+    pub source: String,
+    /// the instruction as the parser read it, in the formatter's assembly
+    pub instr: String,
+    /// values in the order of `REGISTERS`
+    pub registers: Vec<i32>,
+    pub pos_x: i32,
+    pub pos_y: i32,
+    /// tiles collected during this cycle
+    pub collected: Vec<(i32, i32)>,
+}
+
+/// opt-in per cycle recording of a run
+#[derive(Debug, Default)]
+pub struct Trace {
+    pub records: Vec<TraceRecord>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, cycle: usize, ip: usize, source: &str, crab: &Crab, collected: Vec<(i32, i32)>) {
+        self.records.push(TraceRecord {
+            cycle,
+            ip,
+            source: source.to_owned(),
+            instr: crab.code[ip].to_string(),
+            registers: REGISTERS.iter().map(|r| crab.get_reg(*r)).collect(),
+            pos_x: crab.pos_x,
+            pos_y: crab.pos_y,
+            collected,
+        });
+    }
+
+    /// forgets every record after `cycle`, used when stepping back
+    pub fn truncate(&mut self, cycle: usize) {
+        self.records.retain(|r| r.cycle <= cycle);
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W, format: TraceFormat) -> io::Result<()> {
+        match format {
+            TraceFormat::Jsonl => self.write_jsonl(out),
+            TraceFormat::Csv => self.write_csv(out),
+        }
+    }
+
+    fn write_jsonl<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for r in &self.records {
+            write!(out, "{{\"cycle\":{},\"ip\":{},\"line\":{},\"source\":{},\"instr\":{}",
+                r.cycle, r.ip, r.ip + 1, json_str(&r.source), json_str(&r.instr))?;
+            for (reg, val) in REGISTERS.iter().zip(&r.registers) {
+                write!(out, ",\"{:?}\":{}", reg, val)?;
+            }
+            let collected: Vec<_> = r.collected.iter().map(|(x, y)| format!("[{},{}]", x, y)).collect();
+            writeln!(out, ",\"x\":{},\"y\":{},\"rotation\":{},\"collected\":[{}]}}",
+                r.pos_x, r.pos_y, r.rotation(), collected.join(","))?;
+        }
+        Ok(())
+    }
+
+    fn write_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let regs: Vec<_> = REGISTERS.iter().map(|r| format!("{:?}", r)).collect();
+        writeln!(out, "cycle,ip,line,source,instr,{},x,y,rotation,collected", regs.join(","))?;
+        for r in &self.records {
+            let vals: Vec<_> = r.registers.iter().map(|v| v.to_string()).collect();
+            let collected: Vec<_> = r.collected.iter().map(|(x, y)| format!("{}:{}", x, y)).collect();
+            writeln!(out, "{},{},{},{},{},{},{},{},{},{}",
+                r.cycle, r.ip, r.ip + 1, csv_str(&r.source), csv_str(&r.instr), vals.join(","),
+                r.pos_x, r.pos_y, r.rotation(), collected.join(";"))?;
+        }
+        Ok(())
+    }
+}
+
+impl TraceRecord {
+    pub fn rotation(&self) -> i32 {
+        let idx = REGISTERS.iter().position(|r| *r == Register::R).unwrap();
+        self.registers[idx]
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+fn csv_str(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let mut crab = Crab::new();
+        crab.load_code("MOV 1 M\nLBL:").unwrap();
+        crab.step().unwrap();
+        let mut trace = Trace::new();
+        trace.record(1, 0, "MOV 1 M", &crab, vec![]);
+        crab.step().unwrap();
+        // labels take a cycle but do not run the motor
+        trace.record(2, 1, "LBL:", &crab, vec![(3, 3)]);
+        assert_eq!(trace.records[0].instr, "MOV 1 M");
+
+        let mut out = vec![];
+        trace.write(&mut out, TraceFormat::Jsonl).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().nth(1).unwrap(),
+            "{\"cycle\":2,\"ip\":1,\"line\":2,\"source\":\"LBL:\",\"instr\":\"LBL:\",\
+             \"A\":0,\"M\":1,\"H\":0,\"V\":0,\"R\":0,\"x\":3,\"y\":3,\"rotation\":0,\"collected\":[[3,3]]}");
+
+        let mut out = vec![];
+        trace.write(&mut out, TraceFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().next().unwrap(), "cycle,ip,line,source,instr,A,M,H,V,R,x,y,rotation,collected");
+        assert_eq!(out.lines().nth(2).unwrap(), "2,1,2,\"LBL:\",\"LBL:\",0,1,0,0,0,3,3,0,3:3");
+    }
+}
@@ -0,0 +1,234 @@
+//! buttons, labels and panels laid out in rectangles, and what the mouse and keyboard do to them
+//!
+//! the game draws them, this only keeps their state
+
+/// how long the mouse has to rest on a widget before its tooltip shows, in milliseconds
+const TOOLTIP_DELAY: f64 = 500.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Bounds {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Bounds {
+        Bounds { x, y, w, h }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    /// text drawn on it, None for buttons that are part of the background image
+    Button(Option<String>),
+    Label(String),
+    /// a background for other widgets
+    Panel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Normal,
+    Hover,
+    /// the mouse went down on it and has not been released yet
+    Pressed,
+    Disabled,
+}
+
+#[derive(Debug, Clone)]
+pub struct Widget<T> {
+    pub id: T,
+    pub kind: Kind,
+    pub bounds: Bounds,
+    pub tooltip: Option<&'static str>,
+    pub enabled: bool,
+    pub visible: bool,
+}
+
+impl<T> Widget<T> {
+    pub fn button(id: T, bounds: Bounds, text: Option<&str>, tooltip: &'static str) -> Widget<T> {
+        Widget { id, kind: Kind::Button(text.map(str::to_owned)), bounds, tooltip: Some(tooltip), enabled: true, visible: true }
+    }
+
+    pub fn label(id: T, bounds: Bounds, text: &str) -> Widget<T> {
+        Widget { id, kind: Kind::Label(text.to_owned()), bounds, tooltip: None, enabled: true, visible: true }
+    }
+
+    pub fn panel(id: T, bounds: Bounds) -> Widget<T> {
+        Widget { id, kind: Kind::Panel, bounds, tooltip: None, enabled: true, visible: true }
+    }
+
+    /// buttons can be hovered, clicked and focused
+    fn interactive(&self) -> bool {
+        self.visible && self.enabled && matches!(self.kind, Kind::Button(_))
+    }
+}
+
+/// the widgets of a screen, later ones are on top
+#[derive(Debug, Clone)]
+pub struct Ui<T> {
+    widgets: Vec<Widget<T>>,
+    hover: Option<usize>,
+    /// milliseconds the mouse has rested on `hover`
+    hover_time: f64,
+    pressed: Option<usize>,
+    focus: Option<usize>,
+}
+
+impl<T: Copy + PartialEq> Ui<T> {
+    pub fn new(widgets: Vec<Widget<T>>) -> Ui<T> {
+        Ui { widgets, hover: None, hover_time: 0., pressed: None, focus: None }
+    }
+
+    pub fn widgets(&self) -> &[Widget<T>] {
+        &self.widgets
+    }
+
+    fn index(&self, id: T) -> usize {
+        self.widgets.iter().position(|w| w.id == id).unwrap()
+    }
+
+    pub fn get_mut(&mut self, id: T) -> &mut Widget<T> {
+        let i = self.index(id);
+        &mut self.widgets[i]
+    }
+
+    pub fn set_text(&mut self, id: T, text: &str) {
+        match &mut self.get_mut(id).kind {
+            Kind::Button(Some(old)) | Kind::Label(old) if old != text => *old = text.to_owned(),
+            _ => {}
+        }
+    }
+
+    /// enables or disables a widget, disabled widgets lose the focus
+    pub fn set_enabled(&mut self, id: T, enabled: bool) {
+        let i = self.index(id);
+        self.widgets[i].enabled = enabled;
+        if !self.widgets[i].interactive() && self.focus == Some(i) {
+            self.focus = None;
+        }
+    }
+
+    /// the topmost button under the mouse
+    fn at(&self, x: f32, y: f32) -> Option<usize> {
+        self.widgets.iter().rposition(|w| w.visible && w.bounds.contains(x, y))
+            .filter(|i| self.widgets[*i].interactive())
+    }
+
+    pub fn state(&self, id: T) -> State {
+        let i = self.index(id);
+        if !self.widgets[i].enabled {
+            State::Disabled
+        } else if self.pressed == Some(i) {
+            State::Pressed
+        } else if self.hover == Some(i) {
+            State::Hover
+        } else {
+            State::Normal
+        }
+    }
+
+    pub fn focused(&self) -> Option<T> {
+        self.focus.map(|i| self.widgets[i].id)
+    }
+
+    /// counts how long the mouse has rested, `elapsed` is in milliseconds
+    pub fn update(&mut self, elapsed: f64) {
+        self.hover_time += elapsed;
+    }
+
+    pub fn mouse_moved(&mut self, x: f32, y: f32) {
+        let hover = self.at(x, y);
+        if hover != self.hover {
+            self.hover = hover;
+            self.hover_time = 0.;
+        }
+    }
+
+    /// true if the mouse went down on a button
+    pub fn mouse_down(&mut self, x: f32, y: f32) -> bool {
+        self.pressed = self.at(x, y);
+        self.pressed.is_some()
+    }
+
+    /// the button clicked, which is the one the mouse went down on if it is still over it
+    pub fn mouse_up(&mut self, x: f32, y: f32) -> Option<T> {
+        let pressed = self.pressed.take()?;
+        Some(self.widgets[pressed].id).filter(|_| self.at(x, y) == Some(pressed))
+    }
+
+    /// the tooltip to show and the widget it belongs to, once the mouse has rested long enough
+    pub fn tooltip(&self) -> Option<(&'static str, Bounds)> {
+        if self.hover_time < TOOLTIP_DELAY || self.pressed.is_some() {
+            return None;
+        }
+        let widget = &self.widgets[self.hover?];
+        Some((widget.tooltip?, widget.bounds))
+    }
+
+    /// moves the focus to the next button, or the previous one if `back`, and off after the last
+    pub fn focus_next(&mut self, back: bool) {
+        let buttons: Vec<usize> = (0..self.widgets.len()).filter(|i| self.widgets[*i].interactive()).collect();
+        let at = self.focus.and_then(|focus| buttons.iter().position(|i| *i == focus));
+        let next = match (at, back) {
+            (None, false) => Some(0),
+            (None, true) => buttons.len().checked_sub(1),
+            (Some(at), false) => Some(at + 1).filter(|next| *next < buttons.len()),
+            (Some(at), true) => at.checked_sub(1),
+        };
+        self.focus = next.and_then(|next| buttons.get(next).copied());
+    }
+
+    /// true if a button had the focus
+    pub fn clear_focus(&mut self) -> bool {
+        self.focus.take().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ui() {
+        let mut ui = Ui::new(vec![
+            Widget::button(1, Bounds::new(0., 0., 10., 10.), None, "one"),
+            Widget::label(2, Bounds::new(10., 0., 10., 10.), "two"),
+            Widget::button(3, Bounds::new(20., 0., 10., 10.), Some("three"), "three"),
+        ]);
+        ui.mouse_moved(5., 5.);
+        assert_eq!(ui.state(1), State::Hover);
+        assert_eq!(ui.tooltip(), None);
+        ui.update(600.);
+        assert_eq!(ui.tooltip(), Some(("one", Bounds::new(0., 0., 10., 10.))));
+
+        // a click is a press and a release on the same button
+        assert!(ui.mouse_down(5., 5.));
+        assert_eq!(ui.state(1), State::Pressed);
+        assert_eq!(ui.mouse_up(6., 6.), Some(1));
+        assert!(ui.mouse_down(5., 5.));
+        assert_eq!(ui.mouse_up(25., 5.), None);
+        assert!(!ui.mouse_down(15., 5.));
+
+        ui.set_enabled(3, false);
+        assert_eq!(ui.state(3), State::Disabled);
+        assert!(!ui.mouse_down(25., 5.));
+
+        // labels and disabled buttons are skipped
+        ui.focus_next(false);
+        assert_eq!(ui.focused(), Some(1));
+        ui.focus_next(false);
+        assert_eq!(ui.focused(), None);
+        ui.set_enabled(3, true);
+        ui.focus_next(true);
+        assert_eq!(ui.focused(), Some(3));
+        ui.set_enabled(3, false);
+        assert_eq!(ui.focused(), None);
+    }
+}